@@ -1,10 +1,14 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::collections::btree_map::Entry;
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::mem;
 
 use yaml::{self, Int, Float, Bool, String, Yaml};
-use scanner::Marker;
-use settings::YamlSettings;
+use marked_yaml::{self, MarkedNode, MarkedYaml};
+use scanner::{ContextKind, Marker, ScanError, ScanErrorKind};
+use parser::Tag;
+use settings::{DuplicateKeyPolicy, YamlSettings};
 
 #[derive(Debug)]
 pub enum YamlNodeKind {
@@ -14,6 +18,14 @@ pub enum YamlNodeKind {
     Scalar,
 }
 
+// `Entry::or_default`, which needs Rust 1.28
+fn entry_or_default<V: Default>(map: &mut BTreeMap<NodeHandle, V>, key: NodeHandle) -> &mut V {
+    match map.entry(key) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(V::default()),
+    }
+}
+
 pub trait YamlBuilder: Clone {
     type NodeHandle: Copy;
 
@@ -25,6 +37,7 @@ pub trait YamlBuilder: Clone {
     fn new_int(&mut self, value: Int, marker: Marker) -> Self::NodeHandle;
     fn new_string(&mut self, value: String, marker: Marker) -> Self::NodeHandle;
     fn new_bool(&mut self, value: Bool, marker: Marker) -> Self::NodeHandle;
+    fn new_tagged(&mut self, tag: Tag, node: Self::NodeHandle) -> Self::NodeHandle;
 
     fn add_to_sequence(&mut self, sequence: Self::NodeHandle, item: Self::NodeHandle);
     fn close_sequence(&mut self, sequence: Self::NodeHandle);
@@ -34,7 +47,14 @@ pub trait YamlBuilder: Clone {
         mapping: Self::NodeHandle,
         key: Self::NodeHandle,
         item: Self::NodeHandle,
-    );
+    ) -> Result<(), ScanError>;
+    fn merge_into_mapping(
+        &mut self,
+        mapping: Self::NodeHandle,
+        key: Self::NodeHandle,
+        source: Self::NodeHandle,
+        marker: Marker,
+    ) -> Result<(), ScanError>;
     fn close_mapping(&mut self, mapping: Self::NodeHandle);
 
     fn new_document(&mut self, marker: Marker) -> Self::NodeHandle;
@@ -43,6 +63,10 @@ pub trait YamlBuilder: Clone {
     fn get_node_kind(&self, node: Self::NodeHandle) -> YamlNodeKind;
     fn clone_node(&mut self, node: Self::NodeHandle) -> Self::NodeHandle;
     fn is_badvalue(&self, node: Self::NodeHandle) -> bool;
+    fn add_warning(&mut self, warning: ScanError);
+    /// Record where the source text of `node` ends. Nodes end where they
+    /// start until this is called.
+    fn set_end_marker(&mut self, node: Self::NodeHandle, marker: Marker);
 }
 
 type NodeHandle = usize;
@@ -55,20 +79,33 @@ pub struct YamlStandardBuilder<TS> where TS: YamlSettings {
 }
 
 struct YamlStandardBuilderData<TS> where TS: YamlSettings {
-    _settings: TS,
+    settings: TS,
     counter: NodeHandle,
     nodes: BTreeMap<NodeHandle, Yaml>,
+    marks: BTreeMap<NodeHandle, Marker>,
+    // marks of the keys of open mappings, in insertion order, and whether
+    // the key was merged from another mapping with `<<`
+    key_marks: BTreeMap<NodeHandle, Vec<(Marker, bool)>>,
+    // marks of the items of sequences which are not part of another node
+    // yet, to point at the offending item when merging a list of mappings,
+    // only kept if merge keys are enabled
+    item_marks: BTreeMap<NodeHandle, Vec<Marker>>,
     docs: Vec<Yaml>,
+    warnings: Vec<ScanError>,
 }
 
 impl<TS> YamlStandardBuilderData<TS> where TS: YamlSettings {
 
     fn new(settings: &TS) -> Self {
         Self {
-            _settings: settings.clone(),
+            settings: settings.clone(),
             counter: 1,
             nodes: BTreeMap::new(),
+            marks: BTreeMap::new(),
+            key_marks: BTreeMap::new(),
+            item_marks: BTreeMap::new(),
             docs: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -77,12 +114,17 @@ impl<TS> YamlStandardBuilderData<TS> where TS: YamlSettings {
         self.counter
     }
 
-    fn push_node(&mut self, node: Yaml) -> NodeHandle {
+    fn push_node(&mut self, node: Yaml, marker: Marker) -> NodeHandle {
         let handle = self.get_next_handle();
         self.nodes.insert(handle, node);
+        self.marks.insert(handle, marker);
         handle
     }
 
+    fn get_mark(&self, handle: NodeHandle) -> Marker {
+        self.marks[&handle]
+    }
+
     fn get_node(&self, handle: NodeHandle) -> Option<&Yaml> {
         self.nodes.get(&handle)
     }
@@ -92,13 +134,15 @@ impl<TS> YamlStandardBuilderData<TS> where TS: YamlSettings {
     }
 
     fn take_node(&mut self, handle: NodeHandle) -> Option<Yaml> {
+        self.marks.remove(&handle);
+        self.item_marks.remove(&handle);
         self.nodes.remove(&handle)
     }
 
 }
 
 fn move_out_vec<T>(v: &mut Vec<T>) -> Vec<T> {
-    v.drain(..).collect()
+    mem::replace(v, Vec::new())
 }
 
 impl<TS> YamlStandardBuilder<TS> where TS: YamlSettings {
@@ -114,55 +158,73 @@ impl<TS> YamlStandardBuilder<TS> where TS: YamlSettings {
         move_out_vec(&mut self.v.borrow_mut().docs)
     }
 
+    /// Take the warnings recorded so far, e.g. duplicate keys found with
+    /// `DuplicateKeyPolicy::Collect`.
+    pub fn take_warnings(&self) -> Vec<ScanError> {
+        move_out_vec(&mut self.v.borrow_mut().warnings)
+    }
+
 }
 
 impl<TS> YamlBuilder for YamlStandardBuilder<TS> where TS: YamlSettings {
     type NodeHandle = NodeHandle;
 
-    fn new_badvalue(&mut self, _marker: Marker) -> NodeHandle {
+    fn new_badvalue(&mut self, marker: Marker) -> NodeHandle {
         let node = Yaml::BadValue;
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
     }
 
-    fn new_null(&mut self, _marker: Marker) -> NodeHandle {
+    fn new_null(&mut self, marker: Marker) -> NodeHandle {
         let node = Yaml::Null;
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
     }
 
-    fn new_sequence(&mut self, _marker: Marker) -> NodeHandle {
+    fn new_sequence(&mut self, marker: Marker) -> NodeHandle {
         let node = Yaml::Array(YamlSeq::new());
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
     }
 
-    fn new_mapping(&mut self, _marker: Marker) -> NodeHandle {
+    fn new_mapping(&mut self, marker: Marker) -> NodeHandle {
         let node = Yaml::Hash(YamlMap::new());
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
     }
 
-    fn new_float(&mut self, value: Float, _marker: Marker) -> NodeHandle {
+    fn new_float(&mut self, value: Float, marker: Marker) -> NodeHandle {
         let node = Yaml::Real(value.to_string());
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
     }
 
-    fn new_int(&mut self, value: i64, _marker: Marker) -> NodeHandle {
+    fn new_int(&mut self, value: i64, marker: Marker) -> NodeHandle {
         let node = Yaml::Integer(value);
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
     }
 
-    fn new_string(&mut self, value: String, _marker: Marker) -> NodeHandle {
+    fn new_string(&mut self, value: String, marker: Marker) -> NodeHandle {
         let node = Yaml::String(value);
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
     }
 
-    fn new_bool(&mut self, value: bool, _marker: Marker) -> NodeHandle {
+    fn new_bool(&mut self, value: bool, marker: Marker) -> NodeHandle {
         let node = Yaml::Boolean(value);
-        self.v.borrow_mut().push_node(node)
+        self.v.borrow_mut().push_node(node, marker)
+    }
+
+    fn new_tagged(&mut self, tag: Tag, h_node: NodeHandle) -> NodeHandle {
+        let mut dataref = self.v.borrow_mut();
+
+        let marker = dataref.get_mark(h_node);
+        let node = dataref.take_node(h_node).unwrap();
+        dataref.push_node(Yaml::Tagged(tag, Box::new(node)), marker)
     }
 
     fn add_to_sequence(&mut self, h_sequence: NodeHandle, h_item: NodeHandle) {
         let mut dataref = self.v.borrow_mut();
 
+        let item_mark = dataref.get_mark(h_item);
         let item = dataref.take_node(h_item).unwrap();
+        if dataref.settings.is_merge_keys_enabled() {
+            entry_or_default(&mut dataref.item_marks, h_sequence).push(item_mark);
+        }
         let sequence = dataref.get_node_mut(h_sequence).unwrap();
 
         match *sequence {
@@ -178,20 +240,115 @@ impl<TS> YamlBuilder for YamlStandardBuilder<TS> where TS: YamlSettings {
         h_mapping: NodeHandle,
         h_key: NodeHandle,
         h_item: NodeHandle,
-    ) {
+    ) -> Result<(), ScanError> {
         let mut dataref = self.v.borrow_mut();
+        let data = &mut *dataref;
+
+        let key_mark = data.get_mark(h_key);
+        let key = data.take_node(h_key).unwrap();
+        let item = data.take_node(h_item).unwrap();
+        let key_marks = entry_or_default(&mut data.key_marks, h_mapping);
+        let mapping = match *data.nodes.get_mut(&h_mapping).unwrap() {
+            Yaml::Hash(ref mut h) => h,
+            _ => unreachable!(),
+        };
 
-        let key = dataref.take_node(h_key).unwrap();
-        let item = dataref.take_node(h_item).unwrap();
-        let mapping = dataref.get_node_mut(h_mapping).unwrap();
+        if !mapping.contains_key(&key) {
+            mapping.insert(key, item);
+            key_marks.push((key_mark, false));
+            return Ok(());
+        }
 
-        match *mapping {
-            Yaml::Hash(ref mut h) => h.insert(key, item),
+        let index = mapping.keys().position(|k| *k == key).unwrap();
+        let (first_mark, merged) = key_marks[index];
+        if merged {
+            // explicit keys override merged ones
+            *mapping.get_mut(&key).unwrap() = item;
+            key_marks[index] = (key_mark, false);
+            return Ok(());
+        }
+        let err = ScanError::new_with_context(
+            key_mark,
+            ScanErrorKind::DuplicateKey,
+            &format!(
+                "while constructing a mapping, found duplicate key first defined at line {} column {}",
+                first_mark.line(),
+                first_mark.col() + 1
+            ),
+            first_mark,
+            ContextKind::FirstDefinition,
+        );
+        let policy = data.settings.get_duplicate_key_policy();
+        match policy {
+            DuplicateKeyPolicy::Error => return Err(err),
+            DuplicateKeyPolicy::FirstWins => return Ok(()),
+            DuplicateKeyPolicy::LastWins | DuplicateKeyPolicy::Collect => {
+                // inserting moves the key to the back, keep the marks in sync
+                mapping.insert(key, item);
+                key_marks.remove(index);
+                key_marks.push((first_mark, false));
+            }
+        }
+        if policy == DuplicateKeyPolicy::Collect {
+            data.warnings.push(err);
+        }
+        Ok(())
+    }
+
+    fn merge_into_mapping(
+        &mut self,
+        h_mapping: NodeHandle,
+        h_key: NodeHandle,
+        h_source: NodeHandle,
+        marker: Marker,
+    ) -> Result<(), ScanError> {
+        let mut dataref = self.v.borrow_mut();
+        let data = &mut *dataref;
+
+        data.take_node(h_key).unwrap();
+        let item_marks = data.item_marks.remove(&h_source).unwrap_or_default();
+        let sources = match data.take_node(h_source).unwrap() {
+            Yaml::Hash(h) => vec![Yaml::Hash(h)],
+            Yaml::Array(v) => v,
+            _ => {
+                return Err(ScanError::new_with_kind(
+                    marker,
+                    ScanErrorKind::InvalidMerge,
+                    "while constructing a mapping, expected a mapping or a list of mappings for merging",
+                ))
+            }
+        };
+        let key_marks = entry_or_default(&mut data.key_marks, h_mapping);
+        let mapping = match *data.nodes.get_mut(&h_mapping).unwrap() {
+            Yaml::Hash(ref mut h) => h,
             _ => unreachable!(),
         };
+
+        // earlier sources and existing keys win
+        for (i, source) in sources.into_iter().enumerate() {
+            let source = match source {
+                Yaml::Hash(h) => h,
+                _ => {
+                    return Err(ScanError::new_with_kind(
+                        item_marks.get(i).cloned().unwrap_or(marker),
+                        ScanErrorKind::InvalidMerge,
+                        "while constructing a mapping, expected a mapping for merging",
+                    ))
+                }
+            };
+            for (key, item) in source {
+                if !mapping.contains_key(&key) {
+                    mapping.insert(key, item);
+                    key_marks.push((marker, true));
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn close_mapping(&mut self, _h_mapping: NodeHandle) {}
+    fn close_mapping(&mut self, h_mapping: NodeHandle) {
+        self.v.borrow_mut().key_marks.remove(&h_mapping);
+    }
 
     fn new_document(&mut self, _marker: Marker) -> NodeHandle {
         self.v.borrow_mut().get_next_handle()
@@ -202,6 +359,10 @@ impl<TS> YamlBuilder for YamlStandardBuilder<TS> where TS: YamlSettings {
 
         let content = dataref.take_node(h_content).unwrap();
         dataref.docs.push(content);
+        // the remaining nodes, e.g. anchored nodes, belong to the closed
+        // document and are no longer needed
+        dataref.nodes.clear();
+        dataref.marks.clear();
     }
 
     fn get_node_kind(&self, h_node: NodeHandle) -> YamlNodeKind {
@@ -218,7 +379,8 @@ impl<TS> YamlBuilder for YamlStandardBuilder<TS> where TS: YamlSettings {
         let mut dataref = self.v.borrow_mut();
 
         let node2 = dataref.get_node(h_node).unwrap().clone();
-        dataref.push_node(node2)
+        let marker = dataref.get_mark(h_node);
+        dataref.push_node(node2, marker)
     }
 
     fn is_badvalue(&self, h_node: NodeHandle) -> bool {
@@ -229,4 +391,322 @@ impl<TS> YamlBuilder for YamlStandardBuilder<TS> where TS: YamlSettings {
             _ => false,
         }
     }
+
+    fn add_warning(&mut self, warning: ScanError) {
+        self.v.borrow_mut().warnings.push(warning);
+    }
+
+    fn set_end_marker(&mut self, _h_node: NodeHandle, _marker: Marker) {}
+}
+
+type MarkedYamlMap = marked_yaml::Hash;
+type MarkedYamlSeq = marked_yaml::Array;
+
+/// A builder producing `MarkedYaml` trees, where every node knows where it
+/// was found in the source.
+#[derive(Clone)]
+pub struct YamlMarkedBuilder<TS> where TS: YamlSettings {
+    v: Rc<RefCell<YamlMarkedBuilderData<TS>>>,
+}
+
+struct YamlMarkedBuilderData<TS> where TS: YamlSettings {
+    settings: TS,
+    counter: NodeHandle,
+    nodes: BTreeMap<NodeHandle, MarkedYaml>,
+    // keys of open mappings which were merged from another mapping with `<<`
+    merged_keys: BTreeMap<NodeHandle, HashSet<MarkedYaml>>,
+    // explicit keys of open mappings which override merged keys, they
+    // replace the merged keys when the mapping is closed
+    overriding_keys: BTreeMap<NodeHandle, HashSet<MarkedYaml>>,
+    docs: Vec<MarkedYaml>,
+    warnings: Vec<ScanError>,
+}
+
+impl<TS> YamlMarkedBuilderData<TS> where TS: YamlSettings {
+
+    fn new(settings: &TS) -> Self {
+        Self {
+            settings: settings.clone(),
+            counter: 1,
+            nodes: BTreeMap::new(),
+            merged_keys: BTreeMap::new(),
+            overriding_keys: BTreeMap::new(),
+            docs: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn get_next_handle(&mut self) -> NodeHandle {
+        self.counter += 1;
+        self.counter
+    }
+
+    fn push_node(&mut self, node: MarkedNode, marker: Marker) -> NodeHandle {
+        let handle = self.get_next_handle();
+        self.nodes.insert(handle, MarkedYaml::new(node, marker, marker));
+        handle
+    }
+
+    fn take_node(&mut self, handle: NodeHandle) -> Option<MarkedYaml> {
+        self.nodes.remove(&handle)
+    }
+
+}
+
+impl<TS> YamlMarkedBuilder<TS> where TS: YamlSettings {
+
+    pub fn new(settings: &TS) -> Self {
+        let data = YamlMarkedBuilderData::new(settings);
+        Self {
+            v: Rc::new(RefCell::new(data)),
+        }
+    }
+
+    pub fn into_documents(self) -> Vec<MarkedYaml> {
+        move_out_vec(&mut self.v.borrow_mut().docs)
+    }
+
+    /// Take the warnings recorded so far, see
+    /// `YamlStandardBuilder::take_warnings`.
+    pub fn take_warnings(&self) -> Vec<ScanError> {
+        move_out_vec(&mut self.v.borrow_mut().warnings)
+    }
+
+}
+
+impl<TS> YamlBuilder for YamlMarkedBuilder<TS> where TS: YamlSettings {
+    type NodeHandle = NodeHandle;
+
+    fn new_badvalue(&mut self, marker: Marker) -> NodeHandle {
+        self.v.borrow_mut().push_node(MarkedNode::BadValue, marker)
+    }
+
+    fn new_null(&mut self, marker: Marker) -> NodeHandle {
+        self.v.borrow_mut().push_node(MarkedNode::Null, marker)
+    }
+
+    fn new_sequence(&mut self, marker: Marker) -> NodeHandle {
+        let node = MarkedNode::Array(MarkedYamlSeq::new());
+        self.v.borrow_mut().push_node(node, marker)
+    }
+
+    fn new_mapping(&mut self, marker: Marker) -> NodeHandle {
+        let node = MarkedNode::Hash(MarkedYamlMap::new());
+        self.v.borrow_mut().push_node(node, marker)
+    }
+
+    fn new_float(&mut self, value: Float, marker: Marker) -> NodeHandle {
+        let node = MarkedNode::Real(value.to_string());
+        self.v.borrow_mut().push_node(node, marker)
+    }
+
+    fn new_int(&mut self, value: i64, marker: Marker) -> NodeHandle {
+        self.v.borrow_mut().push_node(MarkedNode::Integer(value), marker)
+    }
+
+    fn new_string(&mut self, value: String, marker: Marker) -> NodeHandle {
+        self.v.borrow_mut().push_node(MarkedNode::String(value), marker)
+    }
+
+    fn new_bool(&mut self, value: bool, marker: Marker) -> NodeHandle {
+        self.v.borrow_mut().push_node(MarkedNode::Boolean(value), marker)
+    }
+
+    fn new_tagged(&mut self, tag: Tag, h_node: NodeHandle) -> NodeHandle {
+        let mut dataref = self.v.borrow_mut();
+
+        let node = dataref.take_node(h_node).unwrap();
+        let (start, end) = (node.start, node.end);
+        let handle = dataref.push_node(MarkedNode::Tagged(tag, Box::new(node)), start);
+        dataref.nodes.get_mut(&handle).unwrap().end = end;
+        handle
+    }
+
+    fn add_to_sequence(&mut self, h_sequence: NodeHandle, h_item: NodeHandle) {
+        let mut dataref = self.v.borrow_mut();
+
+        let item = dataref.take_node(h_item).unwrap();
+        let sequence = dataref.nodes.get_mut(&h_sequence).unwrap();
+
+        match sequence.value {
+            MarkedNode::Array(ref mut v) => v.push(item),
+            _ => unreachable!(),
+        }
+    }
+
+    fn close_sequence(&mut self, _h_sequence: NodeHandle) {}
+
+    fn add_to_mapping(
+        &mut self,
+        h_mapping: NodeHandle,
+        h_key: NodeHandle,
+        h_item: NodeHandle,
+    ) -> Result<(), ScanError> {
+        let mut dataref = self.v.borrow_mut();
+        let data = &mut *dataref;
+
+        let key = data.take_node(h_key).unwrap();
+        let item = data.take_node(h_item).unwrap();
+        let merged_keys = entry_or_default(&mut data.merged_keys, h_mapping);
+        let mapping = match data.nodes.get_mut(&h_mapping).unwrap().value {
+            MarkedNode::Hash(ref mut h) => h,
+            _ => unreachable!(),
+        };
+
+        if !mapping.contains_key(&key) {
+            mapping.insert(key, item);
+            return Ok(());
+        }
+
+        if merged_keys.remove(&key) {
+            // explicit keys override merged ones, keep the position
+            *mapping.get_mut(&key).unwrap() = item;
+            entry_or_default(&mut data.overriding_keys, h_mapping).insert(key);
+            return Ok(());
+        }
+        let first_mark = match data.overriding_keys.get(&h_mapping).and_then(|keys| keys.get(&key)) {
+            Some(overriding_key) => overriding_key.start,
+            None => mapping.keys().find(|k| **k == key).unwrap().start,
+        };
+        let err = ScanError::new_with_context(
+            key.start,
+            ScanErrorKind::DuplicateKey,
+            &format!(
+                "while constructing a mapping, found duplicate key first defined at line {} column {}",
+                first_mark.line(),
+                first_mark.col() + 1
+            ),
+            first_mark,
+            ContextKind::FirstDefinition,
+        );
+        let policy = data.settings.get_duplicate_key_policy();
+        match policy {
+            DuplicateKeyPolicy::Error => return Err(err),
+            DuplicateKeyPolicy::FirstWins => return Ok(()),
+            DuplicateKeyPolicy::LastWins | DuplicateKeyPolicy::Collect => {
+                mapping.insert(key, item);
+            }
+        }
+        if policy == DuplicateKeyPolicy::Collect {
+            data.warnings.push(err);
+        }
+        Ok(())
+    }
+
+    fn merge_into_mapping(
+        &mut self,
+        h_mapping: NodeHandle,
+        h_key: NodeHandle,
+        h_source: NodeHandle,
+        marker: Marker,
+    ) -> Result<(), ScanError> {
+        let mut dataref = self.v.borrow_mut();
+        let data = &mut *dataref;
+
+        data.take_node(h_key).unwrap();
+        let source = data.take_node(h_source).unwrap();
+        let sources = match source.value {
+            MarkedNode::Hash(_) => vec![source],
+            MarkedNode::Array(v) => v,
+            _ => {
+                return Err(ScanError::new_with_kind(
+                    marker,
+                    ScanErrorKind::InvalidMerge,
+                    "while constructing a mapping, expected a mapping or a list of mappings for merging",
+                ))
+            }
+        };
+        let merged_keys = entry_or_default(&mut data.merged_keys, h_mapping);
+        let mapping = match data.nodes.get_mut(&h_mapping).unwrap().value {
+            MarkedNode::Hash(ref mut h) => h,
+            _ => unreachable!(),
+        };
+
+        // earlier sources and existing keys win
+        for source in sources {
+            let source = match source.value {
+                MarkedNode::Hash(h) => h,
+                _ => {
+                    return Err(ScanError::new_with_kind(
+                        source.start,
+                        ScanErrorKind::InvalidMerge,
+                        "while constructing a mapping, expected a mapping for merging",
+                    ))
+                }
+            };
+            for (key, item) in source {
+                if !mapping.contains_key(&key) {
+                    merged_keys.insert(key.clone());
+                    mapping.insert(key, item);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn close_mapping(&mut self, h_mapping: NodeHandle) {
+        let mut dataref = self.v.borrow_mut();
+        let data = &mut *dataref;
+
+        data.merged_keys.remove(&h_mapping);
+        // refer to the overriding keys, in one pass over the mapping
+        if let Some(mut keys) = data.overriding_keys.remove(&h_mapping) {
+            if let MarkedNode::Hash(ref mut mapping) = data.nodes.get_mut(&h_mapping).unwrap().value {
+                let entries = mem::replace(mapping, MarkedYamlMap::new());
+                *mapping = entries.into_iter().map(|(k, v)| (keys.take(&k).unwrap_or(k), v)).collect();
+            }
+        }
+    }
+
+    fn new_document(&mut self, _marker: Marker) -> NodeHandle {
+        self.v.borrow_mut().get_next_handle()
+    }
+
+    fn close_document(&mut self, _h_document: NodeHandle, h_content: NodeHandle) {
+        let mut dataref = self.v.borrow_mut();
+
+        let content = dataref.take_node(h_content).unwrap();
+        dataref.docs.push(content);
+        // the remaining nodes belong to the closed document, see
+        // `YamlStandardBuilder::close_document`
+        dataref.nodes.clear();
+    }
+
+    fn get_node_kind(&self, h_node: NodeHandle) -> YamlNodeKind {
+        let dataref = self.v.borrow();
+        match dataref.nodes[&h_node].value {
+            MarkedNode::Array(..) => YamlNodeKind::Sequence,
+            MarkedNode::Hash(..) => YamlNodeKind::Mapping,
+            _ => YamlNodeKind::Scalar,
+        }
+    }
+
+    fn clone_node(&mut self, h_node: NodeHandle) -> NodeHandle {
+        let mut dataref = self.v.borrow_mut();
+
+        let node2 = dataref.nodes[&h_node].clone();
+        let handle = dataref.get_next_handle();
+        dataref.nodes.insert(handle, node2);
+        handle
+    }
+
+    fn is_badvalue(&self, h_node: NodeHandle) -> bool {
+        self.v.borrow().nodes[&h_node].is_badvalue()
+    }
+
+    fn add_warning(&mut self, warning: ScanError) {
+        self.v.borrow_mut().warnings.push(warning);
+    }
+
+    fn set_end_marker(&mut self, h_node: NodeHandle, marker: Marker) {
+        // a tagged node ends where its content ends
+        fn set_end(node: &mut MarkedYaml, marker: Marker) {
+            node.end = marker;
+            if let MarkedNode::Tagged(_, ref mut inner) = node.value {
+                set_end(inner, marker);
+            }
+        }
+        let mut dataref = self.v.borrow_mut();
+        set_end(dataref.nodes.get_mut(&h_node).unwrap(), marker);
+    }
 }
@@ -0,0 +1,205 @@
+use std::cmp;
+use std::fmt::Write;
+use scanner::{ContextKind, Marker, ScanError};
+
+/// Render `error` as a multi-line diagnostic with the lines of `source` it
+/// refers to.
+///
+/// The token at the error is underlined with `^`. If the error has a context
+/// marker, e.g. the start of an unclosed flow collection, that line is shown
+/// as well and marked with `-`.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::diagnostic::render;
+/// use yaml_rust::loader::yaml_load_from_str;
+///
+/// let s = "a: [1, 2\nb: 3\n";
+/// let err = yaml_load_from_str(s).unwrap_err();
+/// assert_eq!(render(s, &err), "\
+/// error: while parsing a flow sequence, expectd ',' or ']'
+///  --> line 2, column 2
+///   |
+/// 1 | a: [1, 2
+///   |    - while parsing a flow sequence started at line 1, column 4
+/// 2 | b: 3
+///   |  ^
+/// ");
+/// ```
+pub fn render(source: &str, error: &ScanError) -> String {
+    // a byte order mark at the start of a line does not take up a column
+    let lines: Vec<&str> = source
+        .lines()
+        .map(|line| if line.starts_with('\u{feff}') { &line['\u{feff}'.len_utf8()..] } else { line })
+        .collect();
+    let mark = *error.marker();
+    let context = match (error.context_marker(), error.context_kind()) {
+        (Some(&context), Some(kind)) => Some((context, context_label(kind, &context))),
+        _ => None,
+    };
+
+    let last_line = context
+        .as_ref()
+        .map_or(mark.line(), |&(context, _)| cmp::max(context.line(), mark.line()));
+    let width = last_line.to_string().len();
+    let gutter = " ".repeat(width);
+
+    let mut out = String::new();
+    writeln!(out, "error: {}", error.info()).unwrap();
+    writeln!(out, "{}--> line {}, column {}", gutter, mark.line(), mark.col() + 1).unwrap();
+    writeln!(out, "{} |", gutter).unwrap();
+
+    let mut notes = vec![(mark, '^', None)];
+    if let Some((context, label)) = context {
+        notes.push((context, '-', Some(label)));
+    }
+    notes.sort_by_key(|&(m, _, _)| (m.line(), m.col()));
+
+    let mut previous: Option<usize> = None;
+    for (m, underline, label) in notes {
+        match previous {
+            Some(line) if m.line() == line + 2 => {
+                write_line(&mut out, &lines, line + 1, width);
+            }
+            Some(line) if m.line() > line + 2 => {
+                out.push_str("...\n");
+            }
+            _ => {}
+        }
+        // notes on the same line share the source line
+        let text = if previous == Some(m.line()) {
+            lines.get(m.line() - 1).cloned().unwrap_or("")
+        } else {
+            write_line(&mut out, &lines, m.line(), width)
+        };
+        write!(out, "{} | {}", gutter, indentation(text, m.col())).unwrap();
+        out.push_str(&underline.to_string().repeat(token_width(text, m.col())));
+        if let Some(label) = label {
+            write!(out, " {}", label).unwrap();
+        }
+        out.push('\n');
+        previous = Some(m.line());
+    }
+    out
+}
+
+// the source line `line` (starting from 1), or an empty one after the end
+fn write_line<'a>(out: &mut String, lines: &[&'a str], line: usize, width: usize) -> &'a str {
+    let text = lines.get(line - 1).cloned().unwrap_or("");
+    if text.is_empty() {
+        writeln!(out, "{:>width$} |", line, width = width).unwrap();
+    } else {
+        writeln!(out, "{:>width$} | {}", line, text, width = width).unwrap();
+    }
+    text
+}
+
+fn context_label(kind: ContextKind, context: &Marker) -> String {
+    let what = match kind {
+        ContextKind::FlowSequence => "while parsing a flow sequence started",
+        ContextKind::FlowMapping => "while parsing a flow mapping started",
+        ContextKind::FirstDefinition => "first defined",
+    };
+    format!("{} at line {}, column {}", what, context.line(), context.col() + 1)
+}
+
+// keep tabs so that the underline is aligned with the source line
+fn indentation(text: &str, col: usize) -> String {
+    text.chars()
+        .chain(::std::iter::repeat(' '))
+        .take(col)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+// the width of the token at `col`: up to a blank, a flow indicator or a ':'
+// which ends a key
+fn token_width(text: &str, col: usize) -> usize {
+    let chars: Vec<char> = text.chars().skip(col).collect();
+    let width = chars
+        .iter()
+        .enumerate()
+        .take_while(|&(i, &c)| {
+            !(c.is_whitespace()
+                || ",[]{}".contains(c)
+                || (c == ':' && chars.get(i + 1).map_or(true, |c| c.is_whitespace())))
+        })
+        .count();
+    cmp::max(width, 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::render;
+    use loader::{yaml_load_from_str, yaml_load_from_str_with_settings};
+    use settings::{DuplicateKeyPolicy, YamlStandardSettings};
+
+    #[test]
+    fn test_render() {
+        let s = "a: 1\nb: \"x\\q\"\n";
+        let err = yaml_load_from_str(s).unwrap_err();
+        assert_eq!(
+            render(s, &err),
+            "\
+error: while parsing a quoted scalar, found unknown escape character
+ --> line 2, column 4
+  |
+2 | b: \"x\\q\"
+  |    ^^^^^
+"
+        );
+    }
+
+    #[test]
+    fn test_render_context() {
+        let s = (1..12).map(|i| format!("k{}: {}\n", i, i)).collect::<String>() + "k1: x\n";
+        let settings = YamlStandardSettings::new().duplicate_keys(DuplicateKeyPolicy::Error);
+        let err = yaml_load_from_str_with_settings(&s, &settings).unwrap_err();
+        assert_eq!(
+            render(&s, &err),
+            "\
+error: while constructing a mapping, found duplicate key first defined at line 1 column 1
+  --> line 12, column 1
+   |
+ 1 | k1: 1
+   | -- first defined at line 1, column 1
+...
+12 | k1: x
+   | ^^
+"
+        );
+
+        // the end of the stream, after the last line
+        let s = "a: {b: 1,\n\tc: [2\n";
+        let err = yaml_load_from_str(s).unwrap_err();
+        assert_eq!(
+            render(s, &err),
+            "\
+error: while parsing a flow sequence, expectd ',' or ']'
+ --> line 3, column 1
+  |
+2 | \tc: [2
+  | \t   - while parsing a flow sequence started at line 2, column 5
+3 |
+  | ^
+"
+        );
+
+        // a byte order mark takes no column
+        let s = "\u{feff}a: [";
+        let err = yaml_load_from_str(s).unwrap_err();
+        assert_eq!(
+            render(s, &err),
+            "\
+error: while parsing a node, did not find expected node content
+ --> line 2, column 1
+  |
+1 | a: [
+  |    - while parsing a flow sequence started at line 1, column 4
+2 |
+  | ^
+"
+        );
+    }
+}
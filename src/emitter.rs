@@ -1,24 +1,38 @@
 use std::convert::From;
 use std::error::Error;
-use std::fmt::{self, Display};
+use std::fmt::{self, Display, Write};
+use std::io;
+use std::cmp;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
 use yaml::{Hash, Yaml};
+use parser::{AnchorId, Tag, CORE_TAG_PREFIX};
+use schema::{ResolvedScalar, Schema};
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum EmitError {
     FmtError(fmt::Error),
     BadHashmapKey,
+    /// Writing to an `io::Write` failed. The error is shared so that `EmitError` stays `Clone`,
+    /// `Send` and `Sync`.
+    Io(Arc<io::Error>),
 }
 
 impl Error for EmitError {
     fn description(&self) -> &str {
         match *self {
-            EmitError::FmtError(ref err) => err.description(),
+            EmitError::FmtError(..) => "error formatting the output",
             EmitError::BadHashmapKey => "bad hashmap key",
+            EmitError::Io(..) => "error writing the output",
         }
     }
 
     fn cause(&self) -> Option<&Error> {
-        None
+        match *self {
+            EmitError::Io(ref err) => Some(&**err),
+            _ => None,
+        }
     }
 }
 
@@ -27,6 +41,7 @@ impl Display for EmitError {
         match *self {
             EmitError::FmtError(ref err) => Display::fmt(err, formatter),
             EmitError::BadHashmapKey => formatter.write_str("bad hashmap key"),
+            EmitError::Io(ref err) => Display::fmt(err, formatter),
         }
     }
 }
@@ -37,16 +52,110 @@ impl From<fmt::Error> for EmitError {
     }
 }
 
+/// How the emitter writes strings which can not be written plain.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StringStyle {
+    /// Double quotes, with escapes. This is the default.
+    DoubleQuoted,
+    /// Single quotes, or double quotes if escapes are needed.
+    SingleQuoted,
+    /// Literal block scalars (`|`) for multi-line strings, and quotes as
+    /// with `SingleQuoted` otherwise.
+    Literal,
+    /// Folded block scalars (`>`) for multi-line strings, wrapped at the
+    /// line width, and quotes as with `SingleQuoted` otherwise.
+    Folded,
+}
+
+/// How the emitter writes non-empty sequences and mappings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollectionStyle {
+    /// Block collections, one entry per line. This is the default.
+    Block,
+    /// Flow collections, e.g. `{a: 1, b: [1, 2]}`.
+    Flow,
+    /// Flow style for collections which contain no other collections and
+    /// fit within the line width, block style otherwise.
+    Hybrid,
+}
+
 pub struct YamlEmitter<'a> {
-    writer: &'a mut fmt::Write,
+    writer: Writer<'a>,
     best_indent: usize,
     compact: bool,
+    string_style: StringStyle,
+    collection_style: CollectionStyle,
+    best_width: usize,
+    explicit_start: bool,
+    explicit_end: bool,
+    version: Option<(u32, u32)>,
+    tag_directives: Vec<(String, String)>,
+    anchors: bool,
+    anchor_min_size: usize,
+    anchor_name: Box<Fn(AnchorId) -> String + 'a>,
 
     level: isize,
+    // the anchors and aliases of the nodes of the current document
+    properties: HashMap<*const Yaml, Property>,
+    // a block scalar without the '-' chomping indicator needs a line break
+    // at the end of the document to keep its last line break
+    needs_final_break: bool,
+}
+
+#[derive(Clone)]
+enum Property {
+    Anchor(String),
+    Alias(String),
 }
 
 pub type EmitResult = Result<(), EmitError>;
 
+struct Writer<'a> {
+    output: Output<'a>,
+    // the column the next character is written at
+    column: usize,
+    // holds the output back while it is measured
+    buffer: Option<String>,
+}
+
+enum Output<'a> {
+    Fmt(&'a mut fmt::Write),
+    // `fmt::Error` can not carry the `io::Error`, so it is kept here
+    Io(&'a mut io::Write, Option<io::Error>),
+}
+
+impl<'a> Writer<'a> {
+    fn new(output: Output<'a>) -> Writer<'a> {
+        Writer {
+            output,
+            column: 0,
+            buffer: None,
+        }
+    }
+}
+
+impl<'a> fmt::Write for Writer<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match s.rfind('\n') {
+            Some(i) => self.column = s[i + 1..].chars().count(),
+            None => self.column += s.chars().count(),
+        }
+        if let Some(ref mut buffer) = self.buffer {
+            buffer.push_str(s);
+            return Ok(());
+        }
+        match self.output {
+            Output::Fmt(ref mut writer) => writer.write_str(s),
+            Output::Io(ref mut writer, ref mut error) => {
+                writer.write_all(s.as_bytes()).map_err(|err| {
+                    *error = Some(err);
+                    fmt::Error
+                })
+            }
+        }
+    }
+}
+
 // from serialize::json
 fn escape_str(wr: &mut fmt::Write, v: &str) -> Result<(), fmt::Error> {
     wr.write_str("\"")?;
@@ -112,11 +221,35 @@ fn escape_str(wr: &mut fmt::Write, v: &str) -> Result<(), fmt::Error> {
 
 impl<'a> YamlEmitter<'a> {
     pub fn new(writer: &'a mut fmt::Write) -> YamlEmitter {
+        YamlEmitter::new_with_writer(Writer::new(Output::Fmt(writer)))
+    }
+
+    /// Create an emitter writing to an `io::Write`, e.g. a file.
+    ///
+    /// The output is written in many small pieces, so unbuffered writers
+    /// should be wrapped in an `io::BufWriter`.
+    pub fn new_with_io_writer(writer: &'a mut io::Write) -> YamlEmitter<'a> {
+        YamlEmitter::new_with_writer(Writer::new(Output::Io(writer, None)))
+    }
+
+    fn new_with_writer(writer: Writer<'a>) -> YamlEmitter<'a> {
         YamlEmitter {
             writer,
             best_indent: 2,
             compact: true,
+            string_style: StringStyle::DoubleQuoted,
+            collection_style: CollectionStyle::Block,
+            best_width: 80,
+            explicit_start: true,
+            explicit_end: false,
+            version: None,
+            tag_directives: Vec::new(),
+            anchors: false,
+            anchor_min_size: 2,
+            anchor_name: Box::new(|id| format!("id{:03}", id)),
             level: -1,
+            properties: HashMap::new(),
+            needs_final_break: false,
         }
     }
 
@@ -126,8 +259,7 @@ impl<'a> YamlEmitter<'a> {
     /// [mappings](http://www.yaml.org/spec/1.2/spec.html#id2798057).
     ///
     /// In this form, blocks cannot have any properties (such as anchors
-    /// or tags), which should be OK, because this emitter doesn't
-    /// (currently) emit those anyways.
+    /// or tags), tagged blocks are always emitted on a new line.
     pub fn compact(&mut self, compact: bool) {
         self.compact = compact;
     }
@@ -137,11 +269,135 @@ impl<'a> YamlEmitter<'a> {
         self.compact
     }
 
+    /// Set how strings which can not be written plain are written.
+    pub fn string_style(&mut self, style: StringStyle) {
+        self.string_style = style;
+    }
+
+    /// Set how non-empty sequences and mappings are written.
+    pub fn collection_style(&mut self, style: CollectionStyle) {
+        self.collection_style = style;
+    }
+
+    /// Set the line width at which folded block scalars are wrapped, 80 by
+    /// default. Lines are only wrapped at spaces, so they can be longer.
+    /// With `CollectionStyle::Hybrid`, flow collections are kept within it.
+    pub fn line_width(&mut self, width: usize) {
+        self.best_width = width;
+    }
+
+    /// Set whether the first document starts with `---`, which is the
+    /// default. The following documents always do.
+    pub fn explicit_start(&mut self, explicit_start: bool) {
+        self.explicit_start = explicit_start;
+    }
+
+    /// Set whether each document ends with `...`. By default they don't.
+    pub fn explicit_end(&mut self, explicit_end: bool) {
+        self.explicit_end = explicit_end;
+    }
+
+    /// Emit a `%YAML` directive with this version before each document.
+    pub fn version_directive(&mut self, version: Option<(u32, u32)>) {
+        self.version = version;
+    }
+
+    /// Emit a `%TAG` directive before each document, and use `handle` for
+    /// the tags starting with `prefix`.
+    pub fn tag_directive(&mut self, handle: &str, prefix: &str) {
+        self.tag_directives.push((handle.to_owned(), prefix.to_owned()));
+    }
+
+    /// Set whether subtrees which occur more than once in a document are
+    /// emitted once with an anchor and then as aliases. Off by default.
+    pub fn anchors(&mut self, anchors: bool) {
+        self.anchors = anchors;
+    }
+
+    /// Set the number of nodes a subtree needs to get an anchor. The
+    /// default of 2 leaves out scalars and empty collections.
+    pub fn anchor_min_size(&mut self, size: usize) {
+        self.anchor_min_size = size;
+    }
+
+    /// Set how anchors are named, `id001`, `id002`, ... by default. This
+    /// also names the anchors of `Yaml::Alias` values.
+    pub fn anchor_name<F: Fn(AnchorId) -> String + 'a>(&mut self, anchor_name: F) {
+        self.anchor_name = Box::new(anchor_name);
+    }
+
     pub fn dump(&mut self, doc: &Yaml) -> EmitResult {
-        // write DocumentStart
-        writeln!(self.writer, "---")?;
-        self.level = -1;
-        self.emit_node(doc)
+        let result = self.dump_documents(&[doc]);
+        self.io_result(result)
+    }
+
+    /// Dump a stream of documents, separated by `---`.
+    pub fn dump_all(&mut self, docs: &[Yaml]) -> EmitResult {
+        let docs: Vec<&Yaml> = docs.iter().collect();
+        let result = self.dump_documents(&docs);
+        self.io_result(result)
+    }
+
+    fn dump_documents(&mut self, docs: &[&Yaml]) -> EmitResult {
+        let directives = self.version.is_some() || !self.tag_directives.is_empty();
+        let mut ended = false;
+        for (i, &doc) in docs.iter().enumerate() {
+            if i > 0 {
+                if !ended {
+                    writeln!(self.writer)?;
+                }
+                // directives can only follow an explicit document end
+                if self.explicit_end || directives {
+                    writeln!(self.writer, "...")?;
+                }
+            }
+            if let Some((major, minor)) = self.version {
+                writeln!(self.writer, "%YAML {}.{}", major, minor)?;
+            }
+            for directive in &self.tag_directives {
+                writeln!(self.writer, "%TAG {} {}", directive.0, directive.1)?;
+            }
+            // write DocumentStart
+            if i > 0 || directives || self.explicit_start {
+                writeln!(self.writer, "---")?;
+            }
+            self.level = -1;
+            if self.anchors {
+                self.find_shared(doc);
+            }
+            let result = match *doc {
+                Yaml::String(ref v) if self.block_style(v).is_some() => self.emit_block_scalar(v),
+                Yaml::Array(_) | Yaml::Hash(_) => match self.emit_flow_collection("", doc) {
+                    Ok(true) => Ok(()),
+                    Ok(false) => self.emit_node(doc),
+                    Err(err) => Err(err),
+                },
+                _ => self.emit_node(doc),
+            };
+            self.properties.clear();
+            result?;
+            ended = mem::replace(&mut self.needs_final_break, false);
+            if ended {
+                writeln!(self.writer)?;
+            }
+        }
+        if self.explicit_end && !docs.is_empty() {
+            if !ended {
+                writeln!(self.writer)?;
+            }
+            write!(self.writer, "...")?;
+        }
+        Ok(())
+    }
+
+    // report the `io::Error` behind a `fmt::Error` of an `io::Write`
+    fn io_result(&mut self, result: EmitResult) -> EmitResult {
+        match (result, &mut self.writer) {
+            (Err(EmitError::FmtError(_)), &mut Writer { output: Output::Io(_, ref mut error), .. }) if error.is_some() => {
+                Err(EmitError::Io(Arc::new(error.take().unwrap())))
+            }
+            (result, _) => result,
+        }
     }
 
     fn write_indent(&mut self) -> EmitResult {
@@ -156,15 +412,30 @@ impl<'a> YamlEmitter<'a> {
         Ok(())
     }
 
+    // a space is needed before the ':' when it would be part of the alias name
+    fn write_key_separator(&mut self, key: &Yaml, space: bool) -> EmitResult {
+        let is_alias = match *key {
+            Yaml::Alias(_) => true,
+            _ => false,
+        };
+        if space || is_alias {
+            write!(self.writer, " ")?;
+        }
+        write!(self.writer, ":")?;
+        Ok(())
+    }
+
     fn emit_node(&mut self, node: &Yaml) -> EmitResult {
         match *node {
             Yaml::Array(ref v) => self.emit_array(v),
             Yaml::Hash(ref h) => self.emit_hash(h),
             Yaml::String(ref v) => {
-                if need_quotes(v) {
-                    escape_str(self.writer, v)?;
-                } else {
+                if !need_quotes(v) {
                     write!(self.writer, "{}", v)?;
+                } else if self.string_style != StringStyle::DoubleQuoted && can_single_quote(v) {
+                    write!(self.writer, "'{}'", v.replace('\'', "''"))?;
+                } else {
+                    escape_str(&mut self.writer, v)?;
                 }
                 Ok(())
             }
@@ -188,9 +459,144 @@ impl<'a> YamlEmitter<'a> {
                 write!(self.writer, "~")?;
                 Ok(())
             }
-            // XXX(chenyh) Alias
-            _ => Ok(()),
+            Yaml::Tagged(ref tag, ref v) => {
+                self.emit_tag(tag)?;
+                self.emit_val(false, v)
+            }
+            Yaml::Alias(id) => {
+                write!(self.writer, "*{}", (self.anchor_name)(id))?;
+                Ok(())
+            }
+        }
+    }
+
+    // give the first occurrence of each shared subtree an anchor, and make
+    // the others aliases to it
+    fn find_shared(&mut self, doc: &Yaml) {
+        let mut occurrences = HashMap::new();
+        let mut max_alias = 0;
+        count_occurrences(doc, &mut occurrences, &mut max_alias);
+        let mut shared: Vec<(usize, Vec<*const Yaml>)> = occurrences
+            .into_iter()
+            .filter(|&(node, (_, ref found))| found.len() > 1 && node_count(node) >= self.anchor_min_size)
+            .map(|(_, occurrence)| occurrence)
+            .collect();
+        // the anchors are numbered in document order
+        shared.sort_by_key(|&(first, _)| first);
+        for (i, (_, found)) in shared.into_iter().enumerate() {
+            let name = (self.anchor_name)(max_alias + i + 1);
+            for (j, node) in found.into_iter().enumerate() {
+                let property = if j == 0 {
+                    Property::Anchor(name.clone())
+                } else {
+                    Property::Alias(name.clone())
+                };
+                self.properties.insert(node, property);
+            }
+        }
+    }
+
+    fn emit_tag(&mut self, tag: &Tag) -> EmitResult {
+        let uri = tag.uri();
+        let shorthand = self
+            .tag_directives
+            .iter()
+            .filter(|directive| uri.len() > directive.1.len() && uri.starts_with(directive.1.as_str()))
+            .max_by_key(|directive| directive.1.len());
+        if let Some(directive) = shorthand {
+            write!(self.writer, "{}{}", directive.0, &uri[directive.1.len()..])?;
+            return Ok(());
+        }
+        // the default handles may be redefined by a directive
+        let handle = match tag.handle.as_ref() {
+            "!" => "!",
+            CORE_TAG_PREFIX => "!!",
+            _ => "",
+        };
+        if !handle.is_empty() && self.tag_directives.iter().any(|directive| directive.0 == handle) {
+            write!(self.writer, "!<{}>", uri)?;
+        } else {
+            write!(self.writer, "{}", tag)?;
+        }
+        Ok(())
+    }
+
+    fn block_style(&self, v: &str) -> Option<char> {
+        let indicator = match self.string_style {
+            StringStyle::Literal => '|',
+            StringStyle::Folded => '>',
+            _ => return None,
+        };
+        let can_block = v.contains('\n')
+            && v.chars().any(|c| c != '\n')
+            && v.chars().all(|c| c != '\r' && is_printable(c));
+        if can_block {
+            Some(indicator)
+        } else {
+            None
+        }
+    }
+
+    fn emit_block_scalar(&mut self, v: &str) -> EmitResult {
+        let indicator = self.block_style(v).unwrap();
+        let lines: Vec<&str> = v.split('\n').collect();
+        let indent = " ".repeat(cmp::max(self.level, 0) as usize * self.best_indent + self.best_indent);
+
+        write!(self.writer, "{}", indicator)?;
+        // the indentation is detected from the first non-empty line
+        if lines.iter().find(|l| !l.is_empty()).unwrap().starts_with(|c| c == ' ' || c == '\t') {
+            write!(self.writer, "{}", self.best_indent)?;
+        }
+        if !v.ends_with('\n') {
+            write!(self.writer, "-")?;
+        } else if v.ends_with("\n\n") {
+            write!(self.writer, "+")?;
+        }
+
+        let is_text = |line: &str| !line.is_empty() && !line.starts_with(|c| c == ' ' || c == '\t');
+        for (i, line) in lines.iter().enumerate() {
+            // the last line break is written after the scalar
+            if i + 1 == lines.len() && line.is_empty() {
+                self.needs_final_break = true;
+                break;
+            }
+            writeln!(self.writer)?;
+            // a single line break between text lines would be folded
+            if indicator == '>'
+                && i > 0
+                && is_text(lines[i - 1])
+                && lines[i..].iter().find(|l| !l.is_empty()).map_or(false, |l| is_text(l))
+            {
+                writeln!(self.writer)?;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            write!(self.writer, "{}", indent)?;
+            if indicator == '>' && is_text(line) {
+                self.write_folded(line, &indent)?;
+            } else {
+                write!(self.writer, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    // write a line of a folded scalar, breaking it at single spaces after
+    // the line width
+    fn write_folded(&mut self, line: &str, indent: &str) -> EmitResult {
+        let chars: Vec<char> = line.chars().collect();
+        let mut column = indent.len();
+        for (i, &c) in chars.iter().enumerate() {
+            if c == ' ' && column > self.best_width && chars.get(i + 1).map_or(false, |&n| n != ' ') {
+                write!(self.writer, "\n{}", indent)?;
+                column = indent.len();
+            } else {
+                self.writer.write_char(c)?;
+                column += 1;
+            }
         }
+        Ok(())
     }
 
     fn emit_array(&mut self, v: &[Yaml]) -> EmitResult {
@@ -217,15 +623,11 @@ impl<'a> YamlEmitter<'a> {
         } else {
             self.level += 1;
             for (cnt, (k, v)) in h.iter().enumerate() {
-                let complex_key = match *k {
-                    Yaml::Hash(_) | Yaml::Array(_) => true,
-                    _ => false,
-                };
                 if cnt > 0 {
                     writeln!(self.writer)?;
                     self.write_indent()?;
                 }
-                if complex_key {
+                if is_complex_key(k) {
                     write!(self.writer, "?")?;
                     self.emit_val(true, k)?;
                     writeln!(self.writer)?;
@@ -234,7 +636,7 @@ impl<'a> YamlEmitter<'a> {
                     self.emit_val(true, v)?;
                 } else {
                     self.emit_node(k)?;
-                    write!(self.writer, ":")?;
+                    self.write_key_separator(k, false)?;
                     self.emit_val(false, v)?;
                 }
             }
@@ -248,6 +650,126 @@ impl<'a> YamlEmitter<'a> {
     /// If `inline` is true, then the preceeding characters are distinct
     /// and short enough to respect the compact flag.
     fn emit_val(&mut self, inline: bool, val: &Yaml) -> EmitResult {
+        self.needs_final_break = false;
+        match self.properties.get(&(val as *const Yaml)).cloned() {
+            Some(Property::Alias(name)) => {
+                write!(self.writer, " *{}", name)?;
+                Ok(())
+            }
+            Some(Property::Anchor(name)) => {
+                write!(self.writer, " &{}", name)?;
+                // like tags, anchors can not be used with the compact notation
+                self.emit_unshared_val(false, val)
+            }
+            None => self.emit_unshared_val(inline, val),
+        }
+    }
+
+    // Emit `node` as a flow collection after `prefix`, if the collection
+    // style allows it. Returns whether it was emitted.
+    fn emit_flow_collection(&mut self, prefix: &str, node: &Yaml) -> Result<bool, EmitError> {
+        match self.collection_style {
+            CollectionStyle::Block => Ok(false),
+            CollectionStyle::Flow => {
+                self.writer.write_str(prefix)?;
+                self.emit_flow(node)?;
+                Ok(true)
+            }
+            CollectionStyle::Hybrid => {
+                if !self.is_flat(node) {
+                    return Ok(false);
+                }
+                let column = self.writer.column;
+                self.writer.buffer = Some(String::new());
+                let result = self.emit_flow(node);
+                let flow = self.writer.buffer.take().unwrap();
+                self.writer.column = column;
+                result?;
+                if column + prefix.len() + flow.chars().count() > self.best_width {
+                    return Ok(false);
+                }
+                write!(self.writer, "{}{}", prefix, flow)?;
+                Ok(true)
+            }
+        }
+    }
+
+    // whether `node` is a collection which contains no other collections,
+    // and no strings which are written as block scalars
+    fn is_flat(&self, node: &Yaml) -> bool {
+        match *node {
+            Yaml::Array(ref v) => v.iter().all(|x| self.is_flow_scalar(x)),
+            Yaml::Hash(ref h) => h.iter().all(|(k, v)| self.is_flow_scalar(k) && self.is_flow_scalar(v)),
+            _ => false,
+        }
+    }
+
+    fn is_flow_scalar(&self, node: &Yaml) -> bool {
+        match *node {
+            Yaml::Array(_) | Yaml::Hash(_) => false,
+            Yaml::String(ref v) => self.block_style(v).is_none(),
+            Yaml::Tagged(_, ref v) => self.is_flow_scalar(v),
+            _ => true,
+        }
+    }
+
+    fn emit_flow(&mut self, node: &Yaml) -> EmitResult {
+        match *node {
+            Yaml::Array(ref v) => {
+                write!(self.writer, "[")?;
+                for (cnt, x) in v.iter().enumerate() {
+                    if cnt > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    self.emit_flow_val(x)?;
+                }
+                write!(self.writer, "]")?;
+                Ok(())
+            }
+            Yaml::Hash(ref h) => {
+                write!(self.writer, "{{")?;
+                for (cnt, (k, v)) in h.iter().enumerate() {
+                    if cnt > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    let complex_key = is_complex_key(k);
+                    if complex_key {
+                        write!(self.writer, "? ")?;
+                    }
+                    self.emit_flow_val(k)?;
+                    self.write_key_separator(k, complex_key)?;
+                    write!(self.writer, " ")?;
+                    self.emit_flow_val(v)?;
+                }
+                write!(self.writer, "}}")?;
+                Ok(())
+            }
+            Yaml::Tagged(ref tag, ref v) => {
+                self.emit_tag(tag)?;
+                write!(self.writer, " ")?;
+                self.emit_flow_val(v)
+            }
+            // strings are quoted, block scalars can not be used here
+            _ => self.emit_node(node),
+        }
+    }
+
+    fn emit_flow_val(&mut self, val: &Yaml) -> EmitResult {
+        match self.properties.get(&(val as *const Yaml)).cloned() {
+            Some(Property::Alias(name)) => {
+                write!(self.writer, "*{}", name)?;
+                return Ok(());
+            }
+            Some(Property::Anchor(name)) => write!(self.writer, "&{} ", name)?,
+            None => {}
+        }
+        self.emit_flow(val)
+    }
+
+    fn emit_unshared_val(&mut self, inline: bool, val: &Yaml) -> EmitResult {
+        if (val.is_array() || val.as_hash().is_some()) && self.emit_flow_collection(" ", val)? {
+            return Ok(());
+        }
         match *val {
             Yaml::Array(ref v) => {
                 if (inline && self.compact) || v.is_empty() {
@@ -271,6 +793,10 @@ impl<'a> YamlEmitter<'a> {
                 }
                 self.emit_hash(h)
             }
+            Yaml::String(ref v) if self.block_style(v).is_some() => {
+                write!(self.writer, " ")?;
+                self.emit_block_scalar(v)
+            }
             _ => {
                 write!(self.writer, " ")?;
                 self.emit_node(val)
@@ -290,6 +816,115 @@ pub fn yaml_dump_compact<'a>(writer: &'a mut fmt::Write, doc: &Yaml, compact: bo
     emitter.dump(doc)
 }
 
+/// Dump `docs` as a stream of documents, each starting with `---`.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::{yaml_dump_all, yaml_load_from_str};
+///
+/// let docs = yaml_load_from_str("a: 1\n---\n- b\n").unwrap();
+/// let mut out = String::new();
+/// yaml_dump_all(&mut out, &docs).unwrap();
+/// assert_eq!(out, "---\na: 1\n---\n- b");
+/// ```
+pub fn yaml_dump_all(writer: &mut fmt::Write, docs: &[Yaml]) -> EmitResult {
+    let mut emitter = YamlEmitter::new(writer);
+    emitter.dump_all(docs)
+}
+
+/// Dump `doc` to an `io::Write`, e.g. a file, without building a `String`
+/// first.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::{yaml_dump_to_writer, yaml_load_doc_from_str};
+///
+/// let doc = yaml_load_doc_from_str("a: [1, 2]").unwrap();
+/// let mut out = Vec::new();
+/// yaml_dump_to_writer(&mut out, &doc).unwrap();
+/// assert_eq!(out, b"---\na:\n  - 1\n  - 2");
+/// ```
+pub fn yaml_dump_to_writer(writer: &mut io::Write, doc: &Yaml) -> EmitResult {
+    let mut emitter = YamlEmitter::new_with_io_writer(writer);
+    emitter.dump(doc)
+}
+
+
+// Record where each node occurs: the position of the first occurrence in
+// document order and the addresses of all occurrences. Occurrences after
+// the first are not descended into, as they are emitted as aliases. Scalar
+// keys, tagged or not, are left out, the block style writes them without
+// an anchor.
+fn count_occurrences<'b>(
+    node: &'b Yaml,
+    occurrences: &mut HashMap<&'b Yaml, (usize, Vec<*const Yaml>)>,
+    max_alias: &mut AnchorId,
+) {
+    let position = occurrences.len();
+    {
+        let found = &mut occurrences.entry(node).or_insert_with(|| (position, Vec::new())).1;
+        found.push(node);
+        if found.len() > 1 {
+            return;
+        }
+    }
+    match *node {
+        Yaml::Array(ref v) => {
+            for x in v {
+                count_occurrences(x, occurrences, max_alias);
+            }
+        }
+        Yaml::Hash(ref h) => {
+            for (k, v) in h {
+                if is_complex_key(k) {
+                    count_occurrences(k, occurrences, max_alias);
+                }
+                count_occurrences(v, occurrences, max_alias);
+            }
+        }
+        Yaml::Tagged(_, ref v) => count_occurrences(v, occurrences, max_alias),
+        Yaml::Alias(id) => *max_alias = cmp::max(*max_alias, id),
+        _ => {}
+    }
+}
+
+// whether `key` is a collection, which is written after a '?'
+fn is_complex_key(key: &Yaml) -> bool {
+    match *key {
+        Yaml::Hash(_) | Yaml::Array(_) => true,
+        Yaml::Tagged(_, ref v) => is_complex_key(v),
+        _ => false,
+    }
+}
+
+fn node_count(node: &Yaml) -> usize {
+    match *node {
+        Yaml::Array(ref v) => 1 + v.iter().map(node_count).sum::<usize>(),
+        Yaml::Hash(ref h) => 1 + h.iter().map(|(k, v)| node_count(k) + node_count(v)).sum::<usize>(),
+        Yaml::Tagged(_, ref v) => 1 + node_count(v),
+        _ => 1,
+    }
+}
+
+// the characters which can be written without escapes
+fn is_printable(c: char) -> bool {
+    match c {
+        '\t' | '\n' | '\r' | '\u{85}' => true,
+        '\u{feff}' => false,
+        _ => {
+            (c >= ' ' && c <= '~')
+                || (c >= '\u{a0}' && c <= '\u{d7ff}')
+                || (c >= '\u{e000}' && c <= '\u{fffd}')
+                || c >= '\u{10000}'
+        }
+    }
+}
+
+fn can_single_quote(string: &str) -> bool {
+    string.chars().all(|c| c != '\n' && c != '\r' && is_printable(c))
+}
 
 /// Check if the string requires quoting.
 /// Strings starting with any of the following characters must be quoted.
@@ -310,7 +945,7 @@ fn need_quotes(string: &str) -> bool {
         string.starts_with(' ') || string.ends_with(' ')
     }
 
-    string == ""
+    string.is_empty()
         || need_quotes_spaces(string)
         || string.starts_with(|character: char| match character {
             '&' | '*' | '?' | '|' | '-' | '<' | '>' | '=' | '!' | '%' | '@' => true,
@@ -350,6 +985,7 @@ fn need_quotes(string: &str) -> bool {
         || string.starts_with('.')
         || string.parse::<i64>().is_ok()
         || string.parse::<f64>().is_ok()
+        || Schema::Core.resolve(string) != Some(ResolvedScalar::String)
 }
 
 #[cfg(test)]
@@ -357,6 +993,16 @@ mod test {
     use super::*;
     use loader::{yaml_load_from_str, yaml_load_doc_from_str};
 
+    fn dump_with<F: FnOnce(&mut YamlEmitter)>(docs: &[Yaml], configure: F) -> String {
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            configure(&mut emitter);
+            emitter.dump_all(docs).unwrap();
+        }
+        writer
+    }
+
     #[test]
     fn test_emit_simple() {
         let s = "
@@ -474,7 +1120,7 @@ string3: "false"
 string4: "~"
 null0: ~
 [true, false]: real_bools
-[True, TRUE, False, FALSE, y,Y,yes,Yes,YES,n,N,no,No,NO,on,On,ON,off,Off,OFF]: false_bools
+["True", "TRUE", "False", "FALSE", y,Y,yes,Yes,YES,n,N,no,No,NO,on,On,ON,off,Off,OFF]: false_bools
 bool0: true
 bool1: false"#;
         let expected = r#"---
@@ -620,4 +1266,348 @@ a:
         assert_eq!(s, writer);
     }
 
+    #[test]
+    fn test_emit_io_writer() {
+        let doc = yaml_load_doc_from_str("a: [1, {b: \"c d\"}]\ne: ~").unwrap();
+        let mut expected = String::new();
+        yaml_dump(&mut expected, &doc).unwrap();
+        let mut out = Vec::new();
+        yaml_dump_to_writer(&mut out, &doc).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        struct Full(usize);
+        impl io::Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(io::Error::new(io::ErrorKind::Other, "disk full"));
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        match yaml_dump_to_writer(&mut Full(8), &doc) {
+            Err(err @ EmitError::Io(_)) => {
+                assert_eq!(err.clone().to_string(), "disk full");
+            }
+            res => panic!("unexpected {:?}", res),
+        }
+
+        // like the other errors, it can be sent to another thread
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<EmitError>();
+    }
+
+    #[test]
+    fn test_emit_multi_doc() {
+        let s = "\
+apiVersion: v1
+kind: Service
+metadata:
+  name: web
+---
+apiVersion: apps/v1
+kind: Deployment
+spec:
+  replicas: 2
+---
+~";
+        let docs = yaml_load_from_str(s).unwrap();
+        assert_eq!(dump_with(&docs, |e| e.explicit_start(false)), s);
+
+        let writer = dump_with(&docs[1..], |e| e.explicit_end(true));
+        assert_eq!(writer, "---\napiVersion: apps/v1\nkind: Deployment\nspec:\n  replicas: 2\n...\n---\n~\n...");
+        assert_eq!(yaml_load_from_str(&writer).unwrap(), &docs[1..]);
+
+        let mut writer = String::new();
+        yaml_dump_all(&mut writer, &[]).unwrap();
+        assert_eq!(writer, "");
+    }
+
+    #[test]
+    fn test_emit_directives() {
+        let s = "a: !<tag:example.com,2000:app/foo> x\nb: !local y\nc: !!binary aGk=";
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        let writer = dump_with(&[doc.clone(), Yaml::Integer(1)], |e| {
+            e.explicit_start(false);
+            e.version_directive(Some((1, 2)));
+            e.tag_directive("!e!", "tag:example.com,2000:");
+            e.tag_directive("!", "tag:example.com,2000:local/");
+        });
+        let expected = "\
+%YAML 1.2
+%TAG !e! tag:example.com,2000:
+%TAG ! tag:example.com,2000:local/
+---
+a: !e!app/foo x
+b: !<!local> y
+c: !!binary aGk=
+...
+%YAML 1.2
+%TAG !e! tag:example.com,2000:
+%TAG ! tag:example.com,2000:local/
+---
+1";
+        assert_eq!(writer, expected);
+        let docs = yaml_load_from_str(&writer).unwrap();
+        assert_eq!(docs, vec![doc, Yaml::Integer(1)]);
+    }
+
+    #[test]
+    fn test_emit_anchors() {
+        let s = "
+defaults: &defaults
+  adapter: postgres
+  host: localhost
+development:
+  database: dev
+  settings: *defaults
+test:
+  - *defaults
+  - [x, y]
+  - [x, y]
+  - &name n
+  - *name
+";
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        let writer = dump_with(&[doc.clone()], |e| e.anchors(true));
+        let expected = "---
+defaults: &id001
+  adapter: postgres
+  host: localhost
+development:
+  database: dev
+  settings: *id001
+test:
+  - *id001
+  - &id002
+    - x
+    - y
+  - *id002
+  - n
+  - n";
+        assert_eq!(writer, expected);
+        assert_eq!(yaml_load_doc_from_str(&writer).unwrap(), doc);
+
+        // scalars, and only the outermost of nested shared subtrees
+        let doc = yaml_load_doc_from_str("a: {b: [1, 2]}\nc: {b: [1, 2]}\nd: 1").unwrap();
+        let writer = dump_with(&[doc.clone()], |e| {
+            e.anchors(true);
+            e.anchor_min_size(1);
+            e.anchor_name(|id| format!("n{}", id));
+        });
+        assert_eq!(writer, "---\na: &n1\n  b:\n    - &n2 1\n    - 2\nc: *n1\nd: *n2");
+        assert_eq!(yaml_load_doc_from_str(&writer).unwrap(), doc);
+
+        // a tagged scalar key is written without an anchor, so it is not shared
+        let doc = yaml_load_doc_from_str("- {!t a: 1}\n- x: !t a").unwrap();
+        let writer = dump_with(&[doc.clone()], |e| e.anchors(true));
+        assert_eq!(writer, "---\n- !t a: 1\n- x: !t a");
+        assert_eq!(yaml_load_doc_from_str(&writer).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_emit_alias() {
+        let mut h = Hash::new();
+        h.insert(Yaml::Alias(1), Yaml::Alias(2));
+        let doc = Yaml::Array(vec![Yaml::Alias(1), Yaml::Hash(h.clone()), Yaml::Hash(h)]);
+        assert_eq!(dump_with(&[doc], |e| e.anchors(true)), "---\n- *id001\n- &id003\n  *id001 : *id002\n- *id003");
+    }
+
+    #[test]
+    fn test_emit_string_styles() {
+        let mut h = Hash::new();
+        h.insert(Yaml::from_str("script"), Yaml::String("set -e\nmake\n  all\n".to_owned()));
+        h.insert(Yaml::from_str("quote"), Yaml::String("it's: ok".to_owned()));
+        h.insert(Yaml::String("a\nb".to_owned()), Yaml::String(" x\n\ny".to_owned()));
+        h.insert(Yaml::from_str("kept"), Yaml::Array(vec![Yaml::String("a\n\n".to_owned())]));
+        let doc = Yaml::Hash(h);
+
+        let literal = "---
+script: |
+  set -e
+  make
+    all
+quote: 'it''s: ok'
+\"a\\nb\": |2-
+   x
+
+  y
+kept:
+  - |+
+    a
+
+";
+        assert_eq!(dump_with(&[doc.clone()], |e| e.string_style(StringStyle::Literal)), literal);
+
+        let folded = "---
+script: >
+  set -e
+
+  make
+    all
+quote: 'it''s: ok'
+\"a\\nb\": >2-
+   x
+
+  y
+kept:
+  - >+
+    a
+
+";
+        assert_eq!(dump_with(&[doc.clone()], |e| e.string_style(StringStyle::Folded)), folded);
+        let wrapped = Yaml::String("aaa bbb ccc ddd\nx".to_owned());
+        assert_eq!(dump_with(&[wrapped], |e| {
+            e.string_style(StringStyle::Folded);
+            e.line_width(6);
+        }), "---\n>-\n  aaa bbb\n  ccc ddd\n\n  x");
+
+        let quoted = "---
+script: \"set -e\\nmake\\n  all\\n\"
+quote: 'it''s: ok'
+\"a\\nb\": \" x\\n\\ny\"
+kept:
+  - \"a\\n\\n\"";
+        assert_eq!(dump_with(&[doc], |e| e.string_style(StringStyle::SingleQuoted)), quoted);
+    }
+
+    #[test]
+    fn test_emit_string_styles_round_trip() {
+        let strings = [
+            "a\nb", "a\nb\n", "a\nb\n\n\n", "\na", "\n\n a\n", "a \n b\n\n c  d\n",
+            "\ta\n\tb", "# no comment\n--- no document", "a\r\nb", "a\u{7}\nb", "'\"\n\\",
+            "one two three four five six seven eight nine ten\nnext  line with  double spaces",
+        ];
+        for &style in &[StringStyle::SingleQuoted, StringStyle::Literal, StringStyle::Folded] {
+            let mut docs = vec![];
+            for s in strings.iter() {
+                let value = Yaml::String((*s).to_owned());
+                let mut h = Hash::new();
+                h.insert(Yaml::from_str("k"), value.clone());
+                docs.push(Yaml::Array(vec![Yaml::Hash(h), value.clone()]));
+                docs.push(value);
+            }
+            for doc in &docs {
+                let writer = dump_with(&[doc.clone()], |e| {
+                    e.string_style(style);
+                    e.line_width(10);
+                });
+                let loaded = yaml_load_doc_from_str(&writer).unwrap_or_else(|| panic!("{:?}:\n{}", style, writer));
+                assert_eq!(&loaded, doc, "{:?}:\n{}", style, writer);
+            }
+
+            let writer = dump_with(&docs, |e| {
+                e.string_style(style);
+                e.line_width(10);
+                e.explicit_end(true);
+            });
+            assert_eq!(yaml_load_from_str(&writer).unwrap(), docs, "{:?}:\n{}", style, writer);
+        }
+    }
+
+    #[test]
+    fn test_emit_tagged() {
+        let s = r#"---
+a: !Ref foo
+b: !vault "secret\n"
+c: !!binary aGVsbG8=
+d: !<tag:example.com,2000:app/foo> bar
+e: !Sub
+  f: g
+h: !list
+  - 1
+  - !pair
+    - x
+    - !!set
+      y: ~
+!key k: v
+? !complex
+  - z
+: w"#;
+
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        let mut writer = String::new();
+        yaml_dump(&mut writer, &doc).unwrap();
+
+        assert_eq!(s, writer, "actual:\n\n{}\n", writer);
+    }
+
+    #[test]
+    fn test_emit_flow() {
+        let doc = yaml_load_doc_from_str("a: 1\nb: [1, 2]\nc:\n  d: [x, {}]\n  ? [e]\n  : 'f, g'\n").unwrap();
+        assert_eq!(
+            dump_with(&[doc.clone()], |e| e.collection_style(CollectionStyle::Flow)),
+            "---\n{a: 1, b: [1, 2], c: {d: [x, {}], ? [e] : \"f, g\"}}"
+        );
+
+        let doc = yaml_load_doc_from_str("- !!set {a: ~}\n- !t b\n").unwrap();
+        assert_eq!(dump_with(&[doc], |e| e.collection_style(CollectionStyle::Flow)), "---\n[!!set {a: ~}, !t b]");
+    }
+
+    #[test]
+    fn test_emit_hybrid() {
+        let doc = yaml_load_doc_from_str(
+            "name: app\nports: [80, 443]\nenv: {A: 1, B: 2}\nnested:\n  - [1, 2]\n  - - [3]\nlong: [aaaa, bbbb, cccc]\n",
+        ).unwrap();
+        assert_eq!(
+            dump_with(&[doc.clone()], |e| {
+                e.collection_style(CollectionStyle::Hybrid);
+                e.line_width(23);
+            }),
+            "---
+name: app
+ports: [80, 443]
+env: {A: 1, B: 2}
+nested:
+  - [1, 2]
+  - - [3]
+long:
+  - aaaa
+  - bbbb
+  - cccc"
+        );
+        // `long: [aaaa, bbbb, cccc]` is 24 characters wide
+        assert!(dump_with(&[doc.clone()], |e| {
+            e.collection_style(CollectionStyle::Hybrid);
+            e.line_width(24);
+        }).ends_with("\nlong: [aaaa, bbbb, cccc]"));
+
+        let doc = yaml_load_doc_from_str("[1, 2]").unwrap();
+        assert_eq!(dump_with(&[doc], |e| e.collection_style(CollectionStyle::Hybrid)), "---\n[1, 2]");
+    }
+
+    #[test]
+    fn test_emit_collection_styles_round_trip() {
+        let s = "
+a: !Ref foo
+b: \"x\\ny\"
+c: [1, 2.5, true, ~, '*']
+d:
+  e: {f: g}
+  ? [h, i]
+  : !!set {j: ~}
+k: &a [l, {m: n}]
+o: *a
+";
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        for &style in &[CollectionStyle::Flow, CollectionStyle::Hybrid] {
+            for &width in &[0, 10, 80] {
+                let writer = dump_with(&[doc.clone()], |e| {
+                    e.collection_style(style);
+                    e.line_width(width);
+                });
+                assert_eq!(yaml_load_doc_from_str(&writer), Some(doc.clone()), "{:?}:\n{}", style, writer);
+            }
+        }
+
+        let writer = dump_with(&[doc.clone()], |e| {
+            e.collection_style(CollectionStyle::Flow);
+            e.anchors(true);
+        });
+        assert!(writer.contains("k: &id001 [l, {m: n}], o: *id001"), "{}", writer);
+        assert_eq!(yaml_load_doc_from_str(&writer), Some(doc));
+    }
 }
@@ -52,15 +52,25 @@ pub mod yaml;
 pub mod loader;
 pub mod settings;
 pub mod builder;
+pub mod schema;
+pub mod resolver;
+pub mod marked_yaml;
+pub mod reader;
+pub mod recovery;
+pub mod diagnostic;
 
 // reexport key APIs
-pub use emitter::{EmitError, YamlEmitter, yaml_dump, yaml_dump_compact};
-pub use parser::Event;
-pub use scanner::ScanError;
+pub use emitter::{CollectionStyle, EmitError, StringStyle, YamlEmitter, yaml_dump, yaml_dump_compact, yaml_dump_all, yaml_dump_to_writer};
+pub use parser::{Event, Tag};
+pub use scanner::{ContextKind, Limit, ScanError, ScanErrorKind};
 pub use yaml::Yaml;
-pub use loader::{YamlLoader, yaml_load_from_str, yaml_load_doc_from_str, yaml_load_from_str_safe, yaml_load_doc_from_str_safe};
-pub use settings::{YamlSettings, YamlStandardSettings};
-pub use builder::{YamlBuilder, YamlNodeKind, YamlStandardBuilder};
+pub use loader::{YamlLoader, YamlDocumentIter, yaml_load_from_str, yaml_load_doc_from_str, yaml_load_from_str_safe, yaml_load_doc_from_str_safe};
+pub use settings::{YamlSettings, YamlStandardSettings, DuplicateKeyPolicy};
+pub use builder::{YamlBuilder, YamlNodeKind, YamlStandardBuilder, YamlMarkedBuilder};
+pub use schema::Schema;
+pub use resolver::{TagNode, TagNodeBuilder, TagResolver};
+pub use marked_yaml::{MarkedYaml, MarkedNode};
+pub use reader::LoadError;
 
 #[cfg(test)]
 mod tests {
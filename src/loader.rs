@@ -1,10 +1,16 @@
-use scanner::{Marker, ScanError, TScalarStyle, TokenType};
+use scanner::{Limit, Marker, ScanError, ScanErrorKind, TScalarStyle};
 use parser::*;
 use yaml::Yaml;
 use settings::{YamlSettings, YamlStandardSettings};
-use builder::{YamlBuilder, YamlStandardBuilder, YamlNodeKind};
+use schema::{ResolvedScalar, Schema};
+use builder::{YamlBuilder, YamlStandardBuilder, YamlMarkedBuilder, YamlNodeKind};
+use marked_yaml::MarkedYaml;
+use reader::{Decoder, LoadError};
+use resolver::{BuilderTagNodes, TagResolver};
 
+use std::io;
 use std::mem;
+use std::rc::Rc;
 use std::collections::BTreeMap;
 use std::f64;
 
@@ -15,21 +21,51 @@ pub fn parse_f64(v: &str) -> Option<f64> {
     match v {
         ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Some(f64::INFINITY),
         "-.inf" | "-.Inf" | "-.INF" => Some(f64::NEG_INFINITY),
-        ".nan" | "NaN" | ".NAN" => Some(f64::NAN),
+        ".nan" | "NaN" | ".NaN" | ".NAN" => Some(f64::NAN),
         _ => v.parse::<f64>().ok(),
     }
 }
 
+// the `!` tag, which only marks a node as not subject to implicit resolution
+fn is_non_specific_tag(tag: &Tag) -> bool {
+    tag.handle == "!" && tag.suffix.is_empty()
+}
+
 struct NodeWithAnchor<TB> where TB: YamlBuilder {
     node: TB::NodeHandle,
     anchor: Option<AnchorId>,
+    tag: Option<Tag>,
+    mark: Marker,
+    // number of nodes in the subtree, including the node itself
+    size: usize,
+    // the node is a `<<` merge key
+    is_merge_key: bool,
+    // the current key of this mapping is a `<<` merge key
+    pending_merge: bool,
 }
 
 impl<TB> NodeWithAnchor<TB> where TB: YamlBuilder {
-    fn new( node: TB::NodeHandle, anchor: Option<AnchorId> ) -> Self {
+    fn new( node: TB::NodeHandle, anchor: Option<AnchorId>, mark: Marker ) -> Self {
         Self {
             node,
             anchor,
+            tag: None,
+            mark,
+            size: 1,
+            is_merge_key: false,
+            pending_merge: false,
+        }
+    }
+
+    fn with_size( node: TB::NodeHandle, size: usize, mark: Marker ) -> Self {
+        Self {
+            node,
+            anchor: None,
+            tag: None,
+            mark,
+            size,
+            is_merge_key: false,
+            pending_merge: false,
         }
     }
 }
@@ -37,19 +73,59 @@ impl<TB> NodeWithAnchor<TB> where TB: YamlBuilder {
 pub struct YamlLoader<TS = YamlStandardSettings, TB = YamlStandardBuilder<TS>> where TS: YamlSettings, TB: YamlBuilder {
     settings: TS,
     builder: TB,
+    tag_resolvers: Vec<Rc<TagResolver>>,
     // states
+    schema: Schema,
     doc_stack: Vec<NodeWithAnchor<TB>>,
     key_stack: Vec<TB::NodeHandle>,
-    anchor_map: BTreeMap<AnchorId, TB::NodeHandle>,
+    anchor_map: BTreeMap<AnchorId, (TB::NodeHandle, usize)>,
     doc: Option<TB::NodeHandle>,
+    alias_expansion: usize,
+    nodes: usize,
+    documents: usize,
+    // errors in nodes which did not stop the loading, see `load_from_iter_with_recovery`
+    recovery: bool,
+    errors: Vec<ScanError>,
+    // the error of an event passed to `on_event`, which can not return it
+    error: Option<ScanError>,
 }
 
 impl<TS, TB> MarkedEventReceiver for YamlLoader<TS, TB> where TS: YamlSettings, TB: YamlBuilder {
     fn on_event(&mut self, ev: Event, marker: Marker) {
+        // the loading stops at the first error
+        if self.error.is_none() {
+            if let Err(err) = self.on_spanned_event(ev, marker, marker) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn on_spanned_event(&mut self, ev: Event, marker: Marker, end_marker: Marker) -> Result<(), ScanError> {
         // println!("EV {:?}", ev);
         match ev {
-            Event::DocumentStart => {
+            Event::DocumentStart(version) => {
+                self.documents += 1;
+                if let Some(max_documents) = self.settings.get_max_documents() {
+                    if self.documents > max_documents {
+                        return Err(ScanError::new_with_kind(
+                            marker,
+                            ScanErrorKind::LimitExceeded(Limit::Documents),
+                            &format!(
+                                "while loading a stream, exceeded the limit of {} documents",
+                                max_documents
+                            ),
+                        ));
+                    }
+                }
+                // documents declaring YAML 1.1 get its resolution rules
+                // instead of the 1.2 Core schema
+                self.schema = match (self.settings.get_schema(), version) {
+                    (Schema::Core, Some((1, 1))) => Schema::Yaml11,
+                    (schema, _) => schema,
+                };
                 self.doc = Some(self.builder.new_document(marker));
+                self.alias_expansion = 0;
+                self.nodes = 0;
             }
             Event::DocumentEnd => {
                 let content = match self.doc_stack.len() {
@@ -65,156 +141,429 @@ impl<TS, TB> MarkedEventReceiver for YamlLoader<TS, TB> where TS: YamlSettings,
                     unreachable!()
                 };
                 self.doc = None;
+                // anchors do not reach beyond their document
+                self.anchor_map.clear();
             }
-            Event::SequenceStart(anchor) => {
-                self.doc_stack
-                    .push(NodeWithAnchor::new(self.builder.new_sequence(marker), anchor));
+            Event::SequenceStart(anchor, tag) => {
+                self.add_nodes(1, marker)?;
+                let mut node = NodeWithAnchor::new(self.builder.new_sequence(marker), anchor, marker);
+                node.tag = tag;
+                self.doc_stack.push(node);
             }
             Event::SequenceEnd => {
-                let node = self.doc_stack.pop().unwrap();
+                let mut node = self.doc_stack.pop().unwrap();
                 self.builder.close_sequence(node.node);
-                self.insert_new_node(node, marker);
+                self.builder.set_end_marker(node.node, end_marker);
+                self.wrap_unknown_tag(&mut node, "seq");
+                self.insert_new_node(node, marker)?;
             }
-            Event::MappingStart(anchor) => {
-                self.doc_stack
-                    .push(NodeWithAnchor::new(self.builder.new_mapping(marker), anchor));
+            Event::MappingStart(anchor, tag) => {
+                self.add_nodes(1, marker)?;
+                let mut node = NodeWithAnchor::new(self.builder.new_mapping(marker), anchor, marker);
+                node.tag = tag;
+                self.doc_stack.push(node);
                 self.key_stack.push(self.builder.new_badvalue(marker));
             }
             Event::MappingEnd => {
                 self.key_stack.pop().unwrap();
-                let node = self.doc_stack.pop().unwrap();
+                let mut node = self.doc_stack.pop().unwrap();
                 self.builder.close_mapping(node.node);
-                self.insert_new_node(node, marker);
+                self.builder.set_end_marker(node.node, end_marker);
+                self.wrap_unknown_tag(&mut node, "map");
+                self.insert_new_node(node, marker)?;
             }
             Event::Scalar{value, style, anchor, tag} => {
-                let node = if style != TScalarStyle::Plain {
-                    self.builder.new_string(value, marker)
-                } else if let Some(TokenType::Tag(ref handle, ref suffix)) = tag {
-                    // XXX tag:yaml.org,2002:
-                    if handle == "!!" {
-                        match suffix.as_ref() {
-                            "bool" => {
-                                // "true" or "false"
-                                match value.parse::<bool>() {
-                                    Err(_) => self.builder.new_badvalue(marker),
-                                    Ok(v) => self.builder.new_bool(v, marker),
-                                }
-                            }
-                            "int" => match value.parse::<i64>() {
-                                Err(_) => self.builder.new_badvalue(marker),
-                                Ok(v) => self.builder.new_int(v, marker),
-                            },
-                            "float" => match parse_f64(&value) {
-                                Some(v) => self.builder.new_float(v, marker),
-                                None => self.builder.new_badvalue(marker),
-                            },
-                            "null" => match value.as_ref() {
-                                "~" | "null" => self.builder.new_null(marker),
-                                _ => self.builder.new_badvalue(marker),
-                            },
-                            _ => self.builder.new_string(value, marker),
-                        }
-                    } else {
-                        self.builder.new_string(value, marker)
+                self.add_nodes(1, marker)?;
+                let is_merge_key = self.settings.is_merge_keys_enabled()
+                    && value == "<<"
+                    && match tag {
+                        None => style == TScalarStyle::Plain,
+                        Some(ref tag) => tag.is_core("merge"),
+                    };
+                let node = match self.scalar_to_node(value, style, tag, is_merge_key, marker) {
+                    Ok(node) => node,
+                    Err(err) => {
+                        self.recover(err)?;
+                        self.builder.new_badvalue(marker)
                     }
-                } else {
-                    // Datatype is not specified, or unrecognized
-                    self.str_to_node(&value, marker)
                 };
 
-                self.insert_new_node(NodeWithAnchor::new(node, anchor), marker);
+                self.builder.set_end_marker(node, end_marker);
+                let mut node = NodeWithAnchor::new(node, anchor, marker);
+                node.is_merge_key = is_merge_key;
+                self.insert_new_node(node, marker)?;
             }
             Event::Alias(anchor_id) => {
-                let n = if self.settings.is_aliases_allowed() {
-                    match self.anchor_map.get(&anchor_id) {
-                        Some(&v) => self.builder.clone_node(v),
-                        None => self.builder.new_badvalue(marker),
+                let node = match self.anchor_map.get(&anchor_id) {
+                    Some(&(v, size)) if self.settings.is_aliases_allowed() => {
+                        self.expand_alias(size, marker)?;
+                        self.add_nodes(size, marker)?;
+                        NodeWithAnchor::with_size(self.builder.clone_node(v), size, marker)
+                    }
+                    _ => {
+                        self.add_nodes(1, marker)?;
+                        NodeWithAnchor::new(self.builder.new_badvalue(marker), None, marker)
                     }
-                } else {
-                    self.builder.new_badvalue(marker)
                 };
-                self.insert_new_node(NodeWithAnchor::new(n, None), marker);
+                self.insert_new_node(node, marker)?;
+            }
+            Event::Invalid => {
+                self.add_nodes(1, marker)?;
+                let node = NodeWithAnchor::new(self.builder.new_badvalue(marker), None, marker);
+                self.insert_new_node(node, marker)?;
             }
             _ => { /* ignore */ }
         }
         // println!("DOC {:?}", self.doc_stack);
+        Ok(())
+    }
+
+    fn on_warning(&mut self, warning: ScanError) {
+        self.builder.add_warning(warning);
     }
 }
 
 impl<TS, TB> YamlLoader<TS, TB> where TS: YamlSettings, TB: YamlBuilder {
     
-    fn new(settings: &TS, builder: &TB) -> Self {
+    /// Creates a loader which adds the loaded documents to `builder`.
+    pub fn new(settings: &TS, builder: &TB) -> Self {
         YamlLoader {
             settings: settings.clone(),
             builder: builder.clone(),
+            tag_resolvers: settings.get_tag_resolvers(),
+            schema: settings.get_schema(),
             doc_stack: Vec::new(),
             key_stack: Vec::new(),
             anchor_map: BTreeMap::new(),
             doc: None,
+            alias_expansion: 0,
+            nodes: 0,
+            documents: 0,
+            recovery: false,
+            errors: Vec::new(),
+            error: None,
+        }
+    }
+
+    // in recovery mode, record an error in a node, which is then dropped or
+    // replaced by a bad value
+    fn recover(&mut self, err: ScanError) -> Result<(), ScanError> {
+        match err.kind() {
+            ScanErrorKind::LimitExceeded(_) => Err(err),
+            _ if self.recovery => {
+                self.errors.push(err);
+                Ok(())
+            }
+            _ => Err(err),
+        }
+    }
+
+    fn add_nodes(&mut self, count: usize, marker: Marker) -> Result<(), ScanError> {
+        self.nodes += count;
+        if let Some(max_nodes) = self.settings.get_max_nodes() {
+            if self.nodes > max_nodes {
+                return Err(ScanError::new_with_kind(
+                    marker,
+                    ScanErrorKind::LimitExceeded(Limit::Nodes),
+                    &format!(
+                        "while loading a document, exceeded the limit of {} nodes",
+                        max_nodes
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_alias(&mut self, size: usize, marker: Marker) -> Result<(), ScanError> {
+        self.alias_expansion += size;
+        if let Some(max_alias_expansion) = self.settings.get_max_alias_expansion() {
+            if self.alias_expansion > max_alias_expansion {
+                return Err(ScanError::new_with_kind(
+                    marker,
+                    ScanErrorKind::LimitExceeded(Limit::AliasExpansion),
+                    &format!(
+                        "while expanding an alias, exceeded the limit of {} nodes produced by aliases",
+                        max_alias_expansion
+                    ),
+                ));
+            }
         }
+        Ok(())
+    }
+
+    fn scalar_to_node(
+        &mut self,
+        value: String,
+        style: TScalarStyle,
+        tag: Option<Tag>,
+        is_merge_key: bool,
+        marker: Marker,
+    ) -> Result<TB::NodeHandle, ScanError> {
+        let node = match tag {
+            // Datatype is not specified
+            None if style != TScalarStyle::Plain => self.builder.new_string(value, marker),
+            None => self.resolve_plain_scalar(&value, marker)?,
+            Some(tag) => {
+                if let Some(node) = self.resolve_custom_tag(&tag, &value, marker)? {
+                    node
+                } else if tag.is_core("str")
+                    || tag.is_core("bool")
+                    || tag.is_core("int")
+                    || tag.is_core("float")
+                    || tag.is_core("null")
+                {
+                    if style != TScalarStyle::Plain {
+                        self.builder.new_string(value, marker)
+                    } else {
+                        self.tagged_to_node(&tag.suffix, value, marker)
+                    }
+                } else if is_non_specific_tag(&tag) || is_merge_key {
+                    self.builder.new_string(value, marker)
+                } else {
+                    // unrecognized, keep the tag
+                    let node = self.builder.new_string(value, marker);
+                    self.builder.new_tagged(tag, node)
+                }
+            }
+        };
+        Ok(node)
     }
 
+    /// Resolve the plain scalar `v` with the Core schema.
+    #[deprecated(note = "use `resolve_plain_scalar`, which follows the schema of the settings")]
     pub fn str_to_node(&mut self, v: &str, marker: Marker) -> TB::NodeHandle {
-        if v.starts_with("0x") {
-            if let Ok(i) = i64::from_str_radix(&v[2..], 16) {
-                return self.builder.new_int(i, marker);
+        self.resolve_with_schema(Schema::Core, v, marker).unwrap()
+    }
+
+    /// Resolve the plain scalar `v` with the schema of the current document,
+    /// failing if the schema does not allow the value.
+    pub fn resolve_plain_scalar(&mut self, v: &str, marker: Marker) -> Result<TB::NodeHandle, ScanError> {
+        let schema = self.schema;
+        self.resolve_with_schema(schema, v, marker)
+    }
+
+    fn resolve_with_schema(&mut self, schema: Schema, v: &str, marker: Marker) -> Result<TB::NodeHandle, ScanError> {
+        let node = match schema.resolve(v) {
+            Some(ResolvedScalar::Null) => self.builder.new_null(marker),
+            Some(ResolvedScalar::Bool(b)) => self.builder.new_bool(b, marker),
+            Some(ResolvedScalar::Int(i)) => self.builder.new_int(i, marker),
+            Some(ResolvedScalar::Float(f)) => self.builder.new_float(f, marker),
+            Some(ResolvedScalar::String) => self.builder.new_string(v.to_owned(), marker),
+            None => {
+                return Err(ScanError::new_with_kind(
+                    marker,
+                    ScanErrorKind::InvalidValue,
+                    &format!(
+                        "while resolving a plain scalar, found a value that is not allowed by the {} schema",
+                        schema.name()
+                    ),
+                ))
             }
-        } else if v.starts_with("0o") {
-            if let Ok(i) = i64::from_str_radix(&v[2..], 8) {
-                return self.builder.new_int(i, marker);
+        };
+        Ok(node)
+    }
+
+    fn resolve_custom_tag(
+        &mut self,
+        tag: &Tag,
+        v: &str,
+        marker: Marker,
+    ) -> Result<Option<TB::NodeHandle>, ScanError> {
+        let mut builder = BuilderTagNodes::new(&mut self.builder, marker);
+        for resolver in &self.tag_resolvers {
+            if let Some(node) = resolver.resolve(tag, v, marker, &mut builder) {
+                return Ok(Some(builder.handle(node?)));
             }
-        } else if v.starts_with('+') {
-            if let Ok(i) = v[1..].parse::<i64>() {
-                return self.builder.new_int(i, marker);
+        }
+        Ok(None)
+    }
+
+    fn wrap_unknown_tag(&mut self, node: &mut NodeWithAnchor<TB>, kind: &str) {
+        if let Some(tag) = node.tag.take() {
+            if !tag.is_core(kind) && !is_non_specific_tag(&tag) {
+                node.node = self.builder.new_tagged(tag, node.node);
             }
-        } else if let Ok(i) = v.parse::<i64>() {
-            return self.builder.new_int(i, marker);
-        } else if let Some(f) = parse_f64(v) {
-            return self.builder.new_float(f, marker);
         }
-        match v {
-            "~" | "null" => self.builder.new_null(marker),
-            "true" => self.builder.new_bool(true, marker),
-            "false" => self.builder.new_bool(false, marker),
-            _ => self.builder.new_string(v.to_owned(), marker),
+    }
+
+    fn tagged_to_node(&mut self, suffix: &str, v: String, marker: Marker) -> TB::NodeHandle {
+        // the failsafe schema resolves nothing, fall back to the core rules
+        // for values with an explicit type
+        let schema = match self.schema {
+            Schema::Failsafe => Schema::Core,
+            schema => schema,
+        };
+        match (suffix, schema.resolve(&v)) {
+            ("bool", Some(ResolvedScalar::Bool(b))) => self.builder.new_bool(b, marker),
+            ("int", Some(ResolvedScalar::Int(i))) => self.builder.new_int(i, marker),
+            ("float", Some(ResolvedScalar::Int(i))) => self.builder.new_float(i as f64, marker),
+            ("float", Some(ResolvedScalar::Float(f))) => self.builder.new_float(f, marker),
+            ("null", Some(ResolvedScalar::Null)) => self.builder.new_null(marker),
+            ("bool", _) | ("int", _) | ("float", _) | ("null", _) => self.builder.new_badvalue(marker),
+            _ => self.builder.new_string(v, marker),
         }
     }
 
-    fn insert_new_node(&mut self, node: NodeWithAnchor<TB>, marker: Marker) {
+    fn insert_new_node(&mut self, node: NodeWithAnchor<TB>, marker: Marker) -> Result<(), ScanError> {
         if self.settings.is_aliases_allowed() {
             if let Some(anchor_id) = node.anchor {
                 self.anchor_map
-                    .insert(anchor_id, self.builder.clone_node(node.node));
+                    .insert(anchor_id, (self.builder.clone_node(node.node), node.size));
             }
         }
-        if self.doc_stack.is_empty() {
+        let result = if self.doc_stack.is_empty() {
             self.doc_stack.push(node);
+            Ok(())
         } else {
             let parent = self.doc_stack.last_mut().unwrap();
+            parent.size += node.size;
             match self.builder.get_node_kind(parent.node) {
-                YamlNodeKind::Sequence => self.builder.add_to_sequence(parent.node, node.node),
+                YamlNodeKind::Sequence => {
+                    self.builder.add_to_sequence(parent.node, node.node);
+                    Ok(())
+                }
                 YamlNodeKind::Mapping => {
                     let cur_key = self.key_stack.last_mut().unwrap();
                     // current node is a key
                     if self.builder.is_badvalue(*cur_key) {
                         *cur_key = node.node;
+                        parent.pending_merge = node.is_merge_key;
+                        Ok(())
                     // current node is a value
                     } else {
                         // current node is a value
                         let mut newkey = self.builder.new_badvalue(marker);
                         mem::swap(&mut newkey, cur_key);
-                        self.builder.add_to_mapping(parent.node, newkey, node.node);
+                        if parent.pending_merge {
+                            parent.pending_merge = false;
+                            self.builder
+                                .merge_into_mapping(parent.node, newkey, node.node, node.mark)
+                        } else {
+                            self.builder.add_to_mapping(parent.node, newkey, node.node)
+                        }
                     }
                 }
                 _ => unreachable!(),
             }
-        }
+        };
+        // a duplicate key or a broken merge only drops the entry
+        result.or_else(|err| self.recover(err))
     }
 
     pub fn load_from_iter<T: Iterator<Item = char>>(&mut self, source: T) -> Result<(), ScanError> {
         let mut parser = Parser::new(source, &self.settings);
         parser.load(self, true)?;
-        Ok(())
+        match self.take_error() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Take the error which stopped the loading of events passed to
+    /// `on_event`, which can not return it.
+    pub fn take_error(&mut self) -> Option<ScanError> {
+        self.error.take()
+    }
+
+    /// Load all documents, recovering from errors, see
+    /// `Parser::set_recovery`. An error in a node which is only found while
+    /// loading it, e.g. a duplicate key, drops the entry or makes the node a
+    /// bad value. Returns the errors ordered by their position.
+    pub fn load_from_iter_with_recovery<T: Iterator<Item = char>>(&mut self, source: T) -> Vec<ScanError> {
+        let mut parser = Parser::new(source, &self.settings);
+        parser.set_recovery(true);
+        self.recovery = true;
+        let result = parser.load(self, true);
+        let mut errors = parser.take_errors();
+        errors.append(&mut self.errors);
+        if let Err(err) = result {
+            errors.push(err);
+        }
+        errors.sort_by_key(|err| err.marker().index());
+        errors
+    }
+
+    /// Load from UTF-8, UTF-16 or UTF-32 encoded bytes, decoding them as the
+    /// parser needs them.
+    pub fn load_from_reader<R: io::Read>(&mut self, reader: R) -> Result<(), LoadError> {
+        let mut decoder = Decoder::new(reader);
+        let encoding = decoder.encoding();
+        let res = Parser::new_with_encoding(decoder.by_ref(), &self.settings, encoding).load(self, true);
+        // a decoding error truncates the input, report it instead of the
+        // errors this causes
+        match decoder.take_error() {
+            Some(err) => Err(err),
+            None => Ok(res?),
+        }
+    }
+}
+
+/// Loads the documents of a stream one at a time, each as soon as it ends,
+/// so that only the current document is kept in memory.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::loader::YamlDocumentIter;
+///
+/// let mut docs = YamlDocumentIter::new("a\n---\nb\n---\n[c\n".chars());
+/// assert_eq!(docs.next().unwrap().unwrap().as_str().unwrap(), "a");
+/// assert_eq!(docs.next().unwrap().unwrap().as_str().unwrap(), "b");
+/// assert!(docs.next().unwrap().is_err());
+/// assert!(docs.next().is_none());
+/// ```
+pub struct YamlDocumentIter<T, TS = YamlStandardSettings> where T: Iterator<Item = char>, TS: YamlSettings {
+    parser: Parser<T, TS>,
+    loader: YamlLoader<TS>,
+    builder: YamlStandardBuilder<TS>,
+    done: bool,
+}
+
+impl<T> YamlDocumentIter<T> where T: Iterator<Item = char> {
+    pub fn new(source: T) -> Self {
+        let settings = YamlStandardSettings::new();
+        YamlDocumentIter::new_with_settings(source, &settings)
+    }
+}
+
+impl<T, TS> YamlDocumentIter<T, TS> where T: Iterator<Item = char>, TS: YamlSettings {
+    pub fn new_with_settings(source: T, settings: &TS) -> Self {
+        let builder = YamlStandardBuilder::new(settings);
+        YamlDocumentIter {
+            parser: Parser::new(source, settings),
+            loader: YamlLoader::new(settings, &builder),
+            builder,
+            done: false,
+        }
+    }
+
+    /// Take the warnings collected so far, see
+    /// `yaml_load_from_str_with_warnings`.
+    pub fn take_warnings(&self) -> Vec<ScanError> {
+        self.builder.take_warnings()
+    }
+}
+
+impl<T, TS> Iterator for YamlDocumentIter<T, TS> where T: Iterator<Item = char>, TS: YamlSettings {
+    type Item = Result<Yaml, ScanError>;
+
+    fn next(&mut self) -> Option<Result<Yaml, ScanError>> {
+        if self.done {
+            return None;
+        }
+        // loads the next document, or only the end of the stream
+        if let Err(e) = self.parser.load(&mut self.loader, false) {
+            self.done = true;
+            return Some(Err(e));
+        }
+        match self.builder.clone().into_documents().pop() {
+            Some(doc) => Some(Ok(doc)),
+            None => {
+                self.done = true;
+                None
+            }
+        }
     }
 }
 
@@ -225,6 +574,53 @@ pub fn yaml_load_from_str_with_settings<TS>(source: &str, settings: &TS) -> Resu
     Ok(builder.into_documents())
 }
 
+/// Load all documents and also return the warnings collected while loading,
+/// e.g. duplicate keys found with `DuplicateKeyPolicy::Collect` or documents
+/// declaring a YAML version newer than 1.2.
+pub fn yaml_load_from_str_with_warnings<TS>(source: &str, settings: &TS) -> Result<(Vec<Yaml>, Vec<ScanError>), ScanError> where TS: YamlSettings {
+    let builder = YamlStandardBuilder::new(settings);
+    let mut loader = YamlLoader::new(settings, &builder);
+    loader.load_from_iter(source.chars())?;
+    let warnings = builder.take_warnings();
+    Ok((builder.into_documents(), warnings))
+}
+
+/// Load all documents into `MarkedYaml` trees, which record the source
+/// position of every node.
+pub fn yaml_load_marked_from_str_with_settings<TS>(source: &str, settings: &TS) -> Result<Vec<MarkedYaml>, ScanError> where TS: YamlSettings {
+    let builder = YamlMarkedBuilder::new(settings);
+    let mut loader = YamlLoader::new(settings, &builder);
+    loader.load_from_iter(source.chars())?;
+    Ok(builder.into_documents())
+}
+
+pub fn yaml_load_marked_from_str(source: &str) -> Result<Vec<MarkedYaml>, ScanError> {
+    let settings = YamlStandardSettings::new();
+    yaml_load_marked_from_str_with_settings(source, &settings)
+}
+
+pub fn yaml_load_from_reader_with_settings<R, TS>(reader: R, settings: &TS) -> Result<Vec<Yaml>, LoadError> where R: io::Read, TS: YamlSettings {
+    let builder = YamlStandardBuilder::new(settings);
+    let mut loader = YamlLoader::new(settings, &builder);
+    loader.load_from_reader(reader)?;
+    Ok(builder.into_documents())
+}
+
+/// Load all documents from encoded bytes, e.g. a file, without reading the
+/// whole input first. The encoding is detected as described by the YAML spec.
+pub fn yaml_load_from_reader<R: io::Read>(reader: R) -> Result<Vec<Yaml>, LoadError> {
+    let settings = YamlStandardSettings::new();
+    yaml_load_from_reader_with_settings(reader, &settings)
+}
+
+pub fn yaml_load_from_slice_with_settings<TS>(source: &[u8], settings: &TS) -> Result<Vec<Yaml>, LoadError> where TS: YamlSettings {
+    yaml_load_from_reader_with_settings(source, settings)
+}
+
+pub fn yaml_load_from_slice(source: &[u8]) -> Result<Vec<Yaml>, LoadError> {
+    yaml_load_from_reader(source)
+}
+
 pub fn yaml_load_from_str(source: &str) -> Result<Vec<Yaml>, ScanError> {
     let settings = YamlStandardSettings::new();
     yaml_load_from_str_with_settings(source, &settings)
@@ -252,6 +648,10 @@ pub fn yaml_load_doc_from_str(source: &str) -> Option<Yaml> {
     get_one_doc(yaml_load_from_str(source))
 }
 
+pub fn yaml_load_doc_from_str_with_settings<TS>(source: &str, settings: &TS) -> Option<Yaml> where TS: YamlSettings {
+    get_one_doc(yaml_load_from_str_with_settings(source, settings))
+}
+
 pub fn yaml_load_doc_from_str_safe(source: &str) -> Option<Yaml> {
     get_one_doc(yaml_load_from_str_safe(source))
 }
@@ -0,0 +1,293 @@
+use linked_hash_map::LinkedHashMap;
+use parser::{AnchorId, Tag};
+use loader::parse_f64;
+use scanner::{Marker, START_MARKER};
+use std::hash::{Hash as StdHash, Hasher};
+use std::ops::Index;
+use yaml::{Bool, Int, String, Yaml};
+
+/// A YAML node together with the position of its source text, as built by
+/// `YamlMarkedBuilder`.
+///
+/// Two nodes are equal if their values are equal, the markers are ignored.
+/// This allows looking up keys of a mapping regardless of where they appear.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::loader::yaml_load_marked_from_str;
+///
+/// let docs = yaml_load_marked_from_str("a: 1\nb: [2, 3]\n").unwrap();
+/// let b = &docs[0]["b"];
+/// assert_eq!(b[1].as_i64().unwrap(), 3);
+/// assert_eq!(b.start.line(), 2);
+/// assert_eq!(b.start.col(), 3);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MarkedYaml {
+    pub value: MarkedNode,
+    /// The position of the first character of the node.
+    pub start: Marker,
+    /// The position after the last character of the node.
+    pub end: Marker,
+}
+
+/// The value of a `MarkedYaml` node, see `Yaml` for the meaning of the
+/// variants.
+#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+pub enum MarkedNode {
+    Real(String),
+    Integer(Int),
+    String(String),
+    Boolean(Bool),
+    Array(self::Array),
+    Hash(self::Hash),
+    Alias(AnchorId),
+    Tagged(Tag, Box<MarkedYaml>),
+    Null,
+    BadValue,
+}
+
+pub type Array = Vec<MarkedYaml>;
+pub type Hash = LinkedHashMap<MarkedYaml, MarkedYaml>;
+
+impl PartialEq for MarkedYaml {
+    fn eq(&self, other: &MarkedYaml) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for MarkedYaml {}
+
+impl StdHash for MarkedYaml {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+macro_rules! define_as (
+    ($name:ident, $t:ident, $yt:ident) => (
+pub fn $name(&self) -> Option<$t> {
+    match self.value {
+        MarkedNode::$yt(v) => Some(v),
+        _ => None
+    }
+}
+    );
+);
+
+macro_rules! define_as_ref (
+    ($name:ident, $t:ty, $yt:ident) => (
+pub fn $name(&self) -> Option<$t> {
+    match self.value {
+        MarkedNode::$yt(ref v) => Some(v),
+        _ => None
+    }
+}
+    );
+);
+
+impl MarkedYaml {
+    pub fn new(value: MarkedNode, start: Marker, end: Marker) -> MarkedYaml {
+        MarkedYaml { value, start, end }
+    }
+
+    define_as!(as_bool, bool, Boolean);
+    define_as!(as_i64, i64, Integer);
+
+    define_as_ref!(as_str, &str, String);
+    define_as_ref!(as_hash, &Hash, Hash);
+    define_as_ref!(as_vec, &Array, Array);
+
+    pub fn is_null(&self) -> bool {
+        match self.value {
+            MarkedNode::Null => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_badvalue(&self) -> bool {
+        match self.value {
+            MarkedNode::BadValue => true,
+            _ => false,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.value {
+            MarkedNode::Real(ref v) => parse_f64(v),
+            _ => None,
+        }
+    }
+
+    /// Drop the markers and convert the tree into a plain `Yaml`.
+    pub fn into_yaml(self) -> Yaml {
+        match self.value {
+            MarkedNode::Real(v) => Yaml::Real(v),
+            MarkedNode::Integer(v) => Yaml::Integer(v),
+            MarkedNode::String(v) => Yaml::String(v),
+            MarkedNode::Boolean(v) => Yaml::Boolean(v),
+            MarkedNode::Array(v) => Yaml::Array(v.into_iter().map(MarkedYaml::into_yaml).collect()),
+            MarkedNode::Hash(h) => Yaml::Hash(
+                h.into_iter()
+                    .map(|(k, v)| (k.into_yaml(), v.into_yaml()))
+                    .collect(),
+            ),
+            MarkedNode::Alias(id) => Yaml::Alias(id),
+            MarkedNode::Tagged(tag, node) => Yaml::Tagged(tag, Box::new(node.into_yaml())),
+            MarkedNode::Null => Yaml::Null,
+            MarkedNode::BadValue => Yaml::BadValue,
+        }
+    }
+}
+
+static BAD_VALUE: MarkedYaml = MarkedYaml {
+    value: MarkedNode::BadValue,
+    start: START_MARKER,
+    end: START_MARKER,
+};
+
+impl<'a> Index<&'a str> for MarkedYaml {
+    type Output = MarkedYaml;
+
+    fn index(&self, idx: &'a str) -> &MarkedYaml {
+        let key = MarkedYaml::new(MarkedNode::String(idx.to_owned()), BAD_VALUE.start, BAD_VALUE.end);
+        match self.as_hash() {
+            Some(h) => h.get(&key).unwrap_or(&BAD_VALUE),
+            None => &BAD_VALUE,
+        }
+    }
+}
+
+impl Index<usize> for MarkedYaml {
+    type Output = MarkedYaml;
+
+    fn index(&self, idx: usize) -> &MarkedYaml {
+        if let Some(v) = self.as_vec() {
+            v.get(idx).unwrap_or(&BAD_VALUE)
+        } else if let Some(v) = self.as_hash() {
+            let key = MarkedYaml::new(MarkedNode::Integer(idx as i64), BAD_VALUE.start, BAD_VALUE.end);
+            v.get(&key).unwrap_or(&BAD_VALUE)
+        } else {
+            &BAD_VALUE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use loader::{yaml_load_from_str, yaml_load_marked_from_str,
+                 yaml_load_marked_from_str_with_settings};
+    use settings::{DuplicateKeyPolicy, YamlStandardSettings};
+    use super::MarkedYaml;
+
+    #[test]
+    fn test_marked_positions() {
+        let s = "
+a: 1
+b:
+  - x
+  - {c: true}
+";
+        let docs = yaml_load_marked_from_str(s).unwrap();
+        let doc = &docs[0];
+        assert_eq!(doc.start.line(), 2);
+        assert_eq!(doc["a"].as_i64().unwrap(), 1);
+        assert_eq!((doc["a"].start.line(), doc["a"].start.col()), (2, 3));
+        assert_eq!((doc["b"].start.line(), doc["b"].start.col()), (4, 2));
+        assert_eq!((doc["b"][0].start.line(), doc["b"][0].start.col()), (4, 4));
+        assert_eq!((doc["b"][1].start.line(), doc["b"][1].start.col()), (5, 4));
+        assert!(doc["b"][1]["c"].as_bool().unwrap());
+        assert!(doc["b"][2].is_badvalue());
+        assert!(doc["missing"].is_badvalue());
+
+        // mapping keys carry their position too
+        let keys: Vec<_> = doc.as_hash().unwrap().keys().map(|k| (k.start.line(), k.start.col())).collect();
+        assert_eq!(keys, vec![(2, 0), (3, 0)]);
+        let (key, _) = doc["b"][1].as_hash().unwrap().iter().next().unwrap();
+        assert_eq!((key.start.line(), key.start.col()), (5, 5));
+    }
+
+    #[test]
+    fn test_marked_spans() {
+        let s = "
+a: [1, 'two']
+b:
+  - !x c
+  - |
+    d
+    e
+
+f: g
+";
+        let docs = yaml_load_marked_from_str(s).unwrap();
+        let doc = &docs[0];
+        let span = |n: &MarkedYaml| ((n.start.line(), n.start.col()), (n.end.line(), n.end.col()));
+        assert_eq!(span(doc), ((2, 0), (9, 4)));
+        assert_eq!(span(&doc["a"]), ((2, 3), (2, 13)));
+        assert_eq!(span(&doc["a"][1]), ((2, 7), (2, 12)));
+        assert_eq!(span(&doc["b"]), ((4, 2), (7, 5)));
+        assert_eq!(span(&doc["b"][0]), ((4, 7), (4, 8)));
+        assert_eq!(span(&doc["b"][1]), ((5, 4), (7, 5)));
+        let keys: Vec<_> = doc.as_hash().unwrap().keys().map(span).collect();
+        assert_eq!(keys, vec![((2, 0), (2, 1)), ((3, 0), (3, 1)), ((9, 0), (9, 1))]);
+    }
+
+    #[test]
+    fn test_marked_into_yaml() {
+        let s = "
+- &a {x: 1.5, y: [null, !foo bar]}
+- *a
+- '<<'
+";
+        let marked = yaml_load_marked_from_str(s).unwrap();
+        let plain = yaml_load_from_str(s).unwrap();
+        let converted: Vec<_> = marked.into_iter().map(|d| d.into_yaml()).collect();
+        assert_eq!(converted, plain);
+    }
+
+    #[test]
+    fn test_marked_keys() {
+        let s = "
+base: &base {a: 1, b: 2}
+x:
+  <<: *base
+  b: 3
+  c: 4
+  c: 5
+";
+        let settings = YamlStandardSettings::new()
+            .merge_keys(true)
+            .duplicate_keys(DuplicateKeyPolicy::Error);
+        let err = yaml_load_marked_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 7);
+        assert_eq!(err.context_marker().unwrap().line(), 6);
+
+        let settings = settings.duplicate_keys(DuplicateKeyPolicy::FirstWins);
+        let docs = yaml_load_marked_from_str_with_settings(s, &settings).unwrap();
+        let x = &docs[0]["x"];
+        assert_eq!(x["a"].as_i64().unwrap(), 1);
+        assert_eq!(x["b"].as_i64().unwrap(), 3);
+        assert_eq!(x["c"].as_i64().unwrap(), 4);
+        // the merged value points into the source mapping, the overriding
+        // key to where it was written
+        assert_eq!(x["a"].start.line(), 2);
+        let keys: Vec<_> = x.as_hash().unwrap().keys().map(|k| (k.as_str().unwrap(), k.start.line())).collect();
+        assert_eq!(keys, vec![("a", 2), ("b", 5), ("c", 6)]);
+
+        // a second explicit key is a duplicate of the overriding one
+        let s = "base: &base {a: 1, b: 2}\nx:\n  <<: *base\n  b: 3\n  b: 4\n";
+        let settings = settings.duplicate_keys(DuplicateKeyPolicy::Error);
+        let err = yaml_load_marked_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 5);
+        assert_eq!(err.context_marker().unwrap().line(), 4);
+    }
+
+    #[test]
+    fn test_marked_merge_error() {
+        let settings = YamlStandardSettings::new().merge_keys(true);
+        let err = yaml_load_marked_from_str_with_settings("a:\n  <<:\n    - {b: 1}\n    - 2\n", &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 4);
+        assert_eq!(err.marker().col(), 6);
+    }
+}
@@ -1,6 +1,8 @@
 use scanner::*;
 use settings::YamlSettings;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::mem;
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 enum State {
@@ -32,6 +34,58 @@ enum State {
 
 pub type AnchorId = usize;
 
+/// The prefix of the `!!` tag handle, unless redefined by a `%TAG` directive.
+pub const CORE_TAG_PREFIX: &str = "tag:yaml.org,2002:";
+
+/// A resolved node tag. `handle` holds the prefix the tag handle resolved to,
+/// so `!!str` is handle `tag:yaml.org,2002:` and suffix `str`, and the local
+/// tag `!foo` is handle `!` and suffix `foo`. Other verbatim tags like
+/// `!<tag:example.com,2000:foo>` have an empty handle. The non-specific tag
+/// `!` is handle `!` with an empty suffix.
+#[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+pub struct Tag {
+    pub handle: String,
+    pub suffix: String,
+}
+
+impl Tag {
+    /// Build a tag from its full URI, using the `!` and `!!` handles when
+    /// they apply.
+    pub fn from_uri(uri: &str) -> Tag {
+        let (handle, suffix) = if uri.starts_with(CORE_TAG_PREFIX) {
+            uri.split_at(CORE_TAG_PREFIX.len())
+        } else if uri.starts_with('!') {
+            uri.split_at(1)
+        } else {
+            ("", uri)
+        };
+        Tag {
+            handle: handle.to_owned(),
+            suffix: suffix.to_owned(),
+        }
+    }
+
+    /// The fully resolved tag URI.
+    pub fn uri(&self) -> String {
+        format!("{}{}", self.handle, self.suffix)
+    }
+
+    /// Whether this is the tag `tag:yaml.org,2002:<suffix>`.
+    pub fn is_core(&self, suffix: &str) -> bool {
+        self.handle == CORE_TAG_PREFIX && self.suffix == suffix
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.handle.as_ref() {
+            "!" => write!(formatter, "!{}", self.suffix),
+            CORE_TAG_PREFIX => write!(formatter, "!!{}", self.suffix),
+            _ => write!(formatter, "!<{}{}>", self.handle, self.suffix),
+        }
+    }
+}
+
 /// `Event` is used with the low-level event base parsing API,
 /// see `EventReceiver` trait.
 #[derive(Clone, PartialEq, Debug, Eq)]
@@ -40,18 +94,22 @@ pub enum Event {
     Nothing,
     StreamStart,
     StreamEnd,
-    DocumentStart,
+    /// The version declared with a `%YAML` directive, if any
+    DocumentStart(Option<(u32, u32)>),
     DocumentEnd,
     /// Refer to an anchor ID
     Alias(AnchorId),
     /// Value, style, anchor_id, tag
-    Scalar{value: String, style: TScalarStyle, anchor: Option<AnchorId>, tag: Option<TokenType>},
-    /// Anchor ID
-    SequenceStart(Option<AnchorId>),
+    Scalar{value: String, style: TScalarStyle, anchor: Option<AnchorId>, tag: Option<Tag>},
+    /// Anchor ID, tag
+    SequenceStart(Option<AnchorId>, Option<Tag>),
     SequenceEnd,
-    /// Anchor ID
-    MappingStart(Option<AnchorId>),
+    /// Anchor ID, tag
+    MappingStart(Option<AnchorId>, Option<Tag>),
     MappingEnd,
+    /// A node which could not be parsed, only produced by a parser which
+    /// recovers from errors, see `Parser::set_recovery`
+    Invalid,
 }
 
 impl Event {
@@ -60,7 +118,7 @@ impl Event {
         Event::Scalar{value: "~".to_owned(), style: TScalarStyle::Plain, anchor: None, tag: None}
     }
 
-    fn empty_scalar_with_anchor(anchor: Option<AnchorId>, tag: Option<TokenType>) -> Event {
+    fn empty_scalar_with_anchor(anchor: Option<AnchorId>, tag: Option<Tag>) -> Event {
         Event::Scalar{value: "".to_owned(), style: TScalarStyle::Plain, anchor, tag}
     }
 }
@@ -71,11 +129,31 @@ pub struct Parser<TR, TS> {
     scanner: Scanner<TR>,
     states: Vec<State>,
     state: State,
-    marks: Vec<Marker>,
+    // the start of the open flow collections, the context of errors inside them
+    marks: Vec<(Marker, ContextKind)>,
     token: Option<Token>,
     current: Option<ParsedEventMarker>,
     anchors: HashMap<String, AnchorId>,
     next_anchor_id: AnchorId,
+    documents: usize,
+    depth: usize,
+    // tag handle -> prefix, for the current document
+    tag_handles: HashMap<String, String>,
+    version: Option<(u32, u32)>,
+    warnings: Vec<ScanError>,
+    // end of the last token which consumed any text
+    last_end: Marker,
+    // the tokens consumed since the last event start a node
+    node_started: bool,
+    // the open block collections: the length of `states` when they started,
+    // their column and whether they are an indentless sequence
+    blocks: Vec<(usize, usize, bool)>,
+    // see `set_recovery`
+    recovery: bool,
+    errors: Vec<ScanError>,
+    // in recovery mode, the events of a flow collection are read up to its
+    // end before they are returned, so that a broken one can be replaced
+    pending: VecDeque<ParsedEventMarker>,
 }
 
 pub trait EventReceiver {
@@ -84,6 +162,19 @@ pub trait EventReceiver {
 
 pub trait MarkedEventReceiver {
     fn on_event(&mut self, ev: Event, _mark: Marker);
+
+    /// Like `on_event`, with the position after the last character of the
+    /// event as well. Events without source text, e.g. an empty scalar or
+    /// the end of a block collection, end where they start. Returning an
+    /// error aborts the parsing with it.
+    fn on_spanned_event(&mut self, ev: Event, mark: Marker, _end_mark: Marker) -> Result<(), ScanError> {
+        self.on_event(ev, mark);
+        Ok(())
+    }
+
+    /// Called for problems which do not stop the parsing, e.g. a document
+    /// declaring a newer YAML version than supported.
+    fn on_warning(&mut self, _warning: ScanError) {}
 }
 
 impl<R: EventReceiver> MarkedEventReceiver for R {
@@ -96,6 +187,7 @@ impl<R: EventReceiver> MarkedEventReceiver for R {
 pub struct ParsedEventMarker {
     event: Event, 
     mark: Marker,
+    end_mark: Marker,
 }
 
 impl ParsedEventMarker {
@@ -103,17 +195,52 @@ impl ParsedEventMarker {
         Self {
             event,
             mark,
+            end_mark: mark,
+        }
+    }
+
+    fn new_with_end(event: Event, mark: Marker, end_mark: Marker) -> Self {
+        Self {
+            event,
+            mark,
+            end_mark,
         }
     }
+
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// The position where the event starts.
+    pub fn marker(&self) -> &Marker {
+        &self.mark
+    }
+
+    /// The position after the last character of the event.
+    pub fn end_marker(&self) -> &Marker {
+        &self.end_mark
+    }
+
+    pub fn into_event(self) -> Event {
+        self.event
+    }
 }
 
 pub type ParseResult = Result<ParsedEventMarker, ScanError>;
 
+// a missing token at the end of the stream means the stream ended too early
+fn unexpected_token_kind(tokentype: &TokenType) -> ScanErrorKind {
+    match *tokentype {
+        TokenType::StreamEnd => ScanErrorKind::UnexpectedEof,
+        _ => ScanErrorKind::UnexpectedToken,
+    }
+}
+
 impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
     pub fn new(src: TR, settings: &TS) -> Parser<TR, TS> {
         Parser {
             settings: settings.clone(),
-            scanner: Scanner::new(src),
+            scanner: Scanner::new_with_settings(src, settings),
             states: Vec::new(),
             state: State::StreamStart,
             marks: Vec::new(),
@@ -122,20 +249,66 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
 
             anchors: HashMap::new(),
             next_anchor_id: 1,
+            documents: 0,
+            depth: 0,
+            tag_handles: HashMap::new(),
+            version: None,
+            warnings: Vec::new(),
+            last_end: Marker::new(0, 1, 0),
+            node_started: false,
+            blocks: Vec::new(),
+            recovery: false,
+            errors: Vec::new(),
+            pending: VecDeque::new(),
         }
     }
 
+    /// Creates a parser for input which was decoded from `encoding`, see
+    /// `Scanner::set_encoding`.
+    pub fn new_with_encoding(src: TR, settings: &TS, encoding: TEncoding) -> Parser<TR, TS> {
+        let mut parser = Parser::new(src, settings);
+        parser.scanner.set_encoding(encoding);
+        parser
+    }
+
+    /// Take the warnings found so far, see `MarkedEventReceiver::on_warning`.
+    pub fn take_warnings(&mut self) -> Vec<ScanError> {
+        mem::replace(&mut self.warnings, Vec::new())
+    }
+
+    /// Recover from errors instead of stopping at the first one. The input
+    /// after an error is skipped up to the next line which is not indented
+    /// deeper than the block collection the error is in, or up to the next
+    /// document. The broken node becomes an `Event::Invalid`, or the whole
+    /// flow collection it is in. An error where no node was started, e.g. in
+    /// a mapping key, only skips the input.
+    ///
+    /// The errors are collected, see `take_errors`. Errors because of the
+    /// limits of the settings, or a second document where only one is
+    /// allowed, still stop the parsing.
+    pub fn set_recovery(&mut self, recovery: bool) {
+        self.recovery = recovery;
+        self.scanner.set_recovery(recovery);
+    }
+
+    /// Take the errors recovered from so far, see `set_recovery`.
+    pub fn take_errors(&mut self) -> Vec<ScanError> {
+        mem::replace(&mut self.errors, Vec::new())
+    }
+
     pub fn peek(&mut self) -> Result<&ParsedEventMarker, ScanError> {
         match self.current {
             Some(ref x) => Ok(x),
             None => {
-                self.current = Some(self.next()?);
+                self.current = Some(self.next_event()?);
                 self.peek()
             }
         }
     }
 
-    pub fn next(&mut self) -> ParseResult {
+    /// Parse and return the next event. Unlike `Iterator::next`, this keeps the end marker and
+    /// keeps returning `StreamEnd` once the stream has been fully parsed.
+    pub fn next_event(&mut self) -> ParseResult {
         match self.current {
             None => self.parse(),
             Some(_) => Ok(self.current.take().unwrap()),
@@ -156,7 +329,7 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
         let token = self.scanner.next();
         match token {
             None => match self.scanner.get_error() {
-                None => Err(ScanError::new(self.scanner.mark(), "unexpected eof")),
+                None => Err(ScanError::new_with_kind(self.scanner.mark(), ScanErrorKind::UnexpectedEof, "unexpected eof")),
                 Some(e) => Err(e),
             },
             Some(tok) => Ok(tok),
@@ -164,15 +337,42 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
     }
 
     fn fetch_token(&mut self) -> Token {
-        self.token
+        let token = self
+            .token
             .take()
-            .expect("fetch_token needs to be preceded by peek_token")
+            .expect("fetch_token needs to be preceded by peek_token");
+        self.track_token(&token);
+        token
     }
 
     fn skip(&mut self) {
-        self.token = None;
+        if let Some(token) = self.token.take() {
+            self.track_token(&token);
+        }
         //self.peek_token();
     }
+
+    fn track_token(&mut self, token: &Token) {
+        if token.end_mark != token.mark {
+            self.last_end = token.end_mark;
+        }
+        match token.tokentype {
+            TokenType::BlockEntry | TokenType::Anchor(_) | TokenType::Tag(..) | TokenType::Alias(_) => {
+                self.node_started = true;
+            }
+            _ => {}
+        }
+    }
+    // an unexpected token in a block collection is an indentation error if
+    // it starts a new line
+    fn block_error_kind(&self, mark: Marker) -> ScanErrorKind {
+        if mark.line() > self.last_end.line() {
+            ScanErrorKind::InvalidIndentation
+        } else {
+            ScanErrorKind::UnexpectedToken
+        }
+    }
+
     fn pop_state(&mut self) {
         self.state = self.states.pop().unwrap()
     }
@@ -181,39 +381,190 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
     }
 
     fn parse(&mut self) -> ParseResult {
-        if self.state == State::End {
-            return Ok(ParsedEventMarker::new(Event::StreamEnd, self.scanner.mark()));
+        if let Some(event_marker) = self.pending.pop_front() {
+            return Ok(event_marker);
         }
-        let event_marker = self.state_machine()?;
+        loop {
+            if self.state == State::End {
+                return Ok(ParsedEventMarker::new(Event::StreamEnd, self.scanner.mark()));
+            }
+            let event_marker = match self.parse_event() {
+                Ok(event_marker) => event_marker,
+                Err(e) => match self.recover(e)? {
+                    Some(event_marker) => return Ok(event_marker),
+                    // nothing was started, go on after the skipped input
+                    None => continue,
+                },
+            };
+            match self.state {
+                State::FlowSequenceFirstEntry | State::FlowMappingFirstKey if self.recovery => {
+                    return self.read_flow_collection(event_marker);
+                }
+                _ => return Ok(event_marker),
+            }
+        }
+    }
+
+    // read ahead a flow collection, which is replaced by `Event::Invalid` if
+    // it is broken
+    fn read_flow_collection(&mut self, start: ParsedEventMarker) -> ParseResult {
+        let states = self.states.len();
+        let depth = self.depth - 1;
+        let mut events = VecDeque::new();
+        events.push_back(start);
+        loop {
+            match self.parse_event() {
+                Ok(event_marker) => {
+                    events.push_back(event_marker);
+                    if self.marks.is_empty() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    if !self.is_recoverable(&e) {
+                        return Err(e);
+                    }
+                    self.states.truncate(states);
+                    self.pop_state();
+                    self.marks.clear();
+                    self.depth = depth;
+                    self.resync(e);
+                    return Ok(ParsedEventMarker::new(Event::Invalid, events[0].mark));
+                }
+            }
+        }
+        self.pending = events;
+        Ok(self.pending.pop_front().unwrap())
+    }
+
+    fn is_recoverable(&self, err: &ScanError) -> bool {
+        match err.kind() {
+            ScanErrorKind::LimitExceeded(_) | ScanErrorKind::MultipleDocuments => false,
+            _ => self.recovery,
+        }
+    }
+
+    // go on after an error outside of flow collections, returning the event
+    // which replaces the broken node if one was started
+    fn recover(&mut self, err: ScanError) -> Result<Option<ParsedEventMarker>, ScanError> {
+        if !self.is_recoverable(&err) {
+            return Err(err);
+        }
+        let mark = *err.marker();
+        let replace = match self.blocks.last().cloned() {
+            Some((states, _, _)) => {
+                self.states.truncate(states);
+                let (state, replace) = match self.state {
+                    // the value of the last key is missing
+                    State::BlockMappingValue => (State::BlockMappingKey, true),
+                    State::BlockMappingFirstKey | State::BlockMappingKey => (State::BlockMappingKey, false),
+                    State::BlockSequenceFirstEntry | State::BlockSequenceEntry => {
+                        (State::BlockSequenceEntry, self.node_started)
+                    }
+                    state => (state, self.node_started),
+                };
+                self.state = state;
+                replace
+            }
+            None => match self.state {
+                // the root node
+                State::DocumentContent | State::BlockNode => {
+                    self.pop_state();
+                    true
+                }
+                _ => false,
+            },
+        };
+        self.resync(err);
+        if replace {
+            Ok(Some(ParsedEventMarker::new(Event::Invalid, mark)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // skip the broken input after `err` and record it
+    fn resync(&mut self, err: ScanError) {
+        let indent = self.blocks.last().map_or(-1, |&(_, col, _)| col as isize);
+        let indents: Vec<usize> = self
+            .blocks
+            .iter()
+            .filter(|&&(_, _, indentless)| !indentless)
+            .map(|&(_, col, _)| col)
+            .collect();
+        self.scanner.resync(err.marker(), indent, &indents);
+        self.token = None;
+        self.node_started = false;
+        self.errors.push(err);
+    }
+
+    fn parse_event(&mut self) -> ParseResult {
+        let event_marker = match self.state_machine() {
+            Ok(event_marker) => event_marker,
+            // refer to the innermost flow collection if there is no other context
+            Err(ref e) if e.context_marker().is_none() && !self.marks.is_empty() => {
+                let (context, context_kind) = *self.marks.last().unwrap();
+                return Err(ScanError::new_with_context(*e.marker(), e.kind(), e.info(), context, context_kind));
+            }
+            Err(e) => return Err(e),
+        };
         // println!("EV {:?}", ev);
+        self.track_depth(&event_marker)?;
+        self.node_started = false;
         Ok(event_marker)
     }
 
+    fn track_depth(&mut self, event_marker: &ParsedEventMarker) -> Result<(), ScanError> {
+        match event_marker.event {
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                self.depth += 1;
+                if let Some(max_depth) = self.settings.get_max_depth() {
+                    if self.depth > max_depth {
+                        return Err(ScanError::new_with_kind(
+                            event_marker.mark,
+                            ScanErrorKind::LimitExceeded(Limit::Depth),
+                            &format!(
+                                "while parsing a collection, exceeded the maximum nesting depth of {}",
+                                max_depth
+                            ),
+                        ));
+                    }
+                }
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.depth -= 1;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub fn load<R: MarkedEventReceiver>(
         &mut self,
         recv: &mut R,
         multi: bool,
     ) -> Result<(), ScanError> {
         if !self.scanner.stream_started() {
-            let ParsedEventMarker{event, mark} = self.next()?;
+            let ParsedEventMarker{event, mark, end_mark} = self.next_event()?;
             assert_eq!(event, Event::StreamStart);
-            recv.on_event(event, mark);
+            recv.on_spanned_event(event, mark, end_mark)?;
         }
 
         if self.scanner.stream_ended() {
             // XXX has parsed?
-            recv.on_event(Event::StreamEnd, self.scanner.mark());
+            let mark = self.scanner.mark();
+            recv.on_spanned_event(Event::StreamEnd, mark, mark)?;
             return Ok(());
         }
         loop {
-            let ParsedEventMarker{event, mark} = self.next()?;
+            let ParsedEventMarker{event, mark, end_mark} = self.next_event()?;
             if event == Event::StreamEnd {
-                recv.on_event(event, mark);
+                recv.on_spanned_event(event, mark, end_mark)?;
                 return Ok(());
             }
             // clear anchors before a new document
             self.anchors.clear();
-            self.load_document(event, mark, recv)?;
+            self.load_document(ParsedEventMarker::new_with_end(event, mark, end_mark), recv)?;
             if !multi {
                 break;
             }
@@ -223,80 +574,81 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
 
     fn load_document<R: MarkedEventReceiver>(
         &mut self,
-        first_ev: Event,
-        mark: Marker,
+        first_ev: ParsedEventMarker,
         recv: &mut R,
     ) -> Result<(), ScanError> {
-        assert_eq!(first_ev, Event::DocumentStart);
-        recv.on_event(first_ev, mark);
+        match first_ev.event {
+            Event::DocumentStart(..) => {}
+            _ => unreachable!(),
+        }
+        for warning in self.take_warnings() {
+            recv.on_warning(warning);
+        }
+        recv.on_spanned_event(first_ev.event, first_ev.mark, first_ev.end_mark)?;
 
-        let ParsedEventMarker{event, mark} = self.next()?;
-        self.load_node(event, mark, recv)?;
+        let ev = self.next_event()?;
+        self.load_node(ev, recv)?;
 
         // DOCUMENT-END is expected.
-        let ParsedEventMarker{event, mark} = self.next()?;
+        let ParsedEventMarker{event, mark, end_mark} = self.next_event()?;
         assert_eq!(event, Event::DocumentEnd);
-        recv.on_event(event, mark);
+        recv.on_spanned_event(event, mark, end_mark)?;
 
         Ok(())
     }
 
     fn load_node<R: MarkedEventReceiver>(
         &mut self,
-        first_ev: Event,
-        mark: Marker,
+        first_ev: ParsedEventMarker,
         recv: &mut R,
     ) -> Result<(), ScanError> {
-        match first_ev {
-            Event::Alias(..) | Event::Scalar{..} => {
-                recv.on_event(first_ev, mark);
+        let ParsedEventMarker{event, mark, end_mark} = first_ev;
+        match event {
+            Event::Alias(..) | Event::Scalar{..} | Event::Invalid => {
+                recv.on_spanned_event(event, mark, end_mark)?;
                 Ok(())
             }
-            Event::SequenceStart(_) => {
-                recv.on_event(first_ev, mark);
+            Event::SequenceStart(..) => {
+                recv.on_spanned_event(event, mark, end_mark)?;
                 self.load_sequence(recv)
             }
-            Event::MappingStart(_) => {
-                recv.on_event(first_ev, mark);
+            Event::MappingStart(..) => {
+                recv.on_spanned_event(event, mark, end_mark)?;
                 self.load_mapping(recv)
             }
             _ => {
-                println!("UNREACHABLE EVENT: {:?}", first_ev);
+                println!("UNREACHABLE EVENT: {:?}", event);
                 unreachable!();
             }
         }
     }
 
     fn load_mapping<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let ParsedEventMarker{event: mut key_event, mark: mut key_mark} = self.next()?;
-        while key_event != Event::MappingEnd {
+        let mut key_ev = self.next_event()?;
+        while key_ev.event != Event::MappingEnd {
             // key
-            self.load_node(key_event, key_mark, recv)?;
+            self.load_node(key_ev, recv)?;
 
             // value
-            let ParsedEventMarker{event, mark} = self.next()?;
-            self.load_node(event, mark, recv)?;
+            let ev = self.next_event()?;
+            self.load_node(ev, recv)?;
 
             // next event
-            let ParsedEventMarker{event, mark} = self.next()?;
-            key_event = event;
-            key_mark = mark;
+            key_ev = self.next_event()?;
         }
-        recv.on_event(key_event, key_mark);
+        recv.on_spanned_event(key_ev.event, key_ev.mark, key_ev.end_mark)?;
         Ok(())
     }
 
     fn load_sequence<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let ParsedEventMarker{mut event, mut mark} = self.next()?;
-        while event != Event::SequenceEnd {
-            self.load_node(event, mark, recv)?;
+        let mut ev = self.next_event()?;
+        while ev.event != Event::SequenceEnd {
+            self.load_node(ev, recv)?;
 
             // next event
-            let ParsedEventMarker{event: next_event, mark: next_mark} = self.next()?;
-            event = next_event;
-            mark = next_mark;
+            ev = self.next_event()?;
         }
-        recv.on_event(event, mark);
+        recv.on_spanned_event(ev.event, ev.mark, ev.end_mark)?;
         Ok(())
     }
 
@@ -342,12 +694,12 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
 
     fn stream_start(&mut self) -> ParseResult {
         match *self.peek_token()? {
-            Token{tokentype: TokenType::StreamStart(_), mark} => {
+            Token{tokentype: TokenType::StreamStart(_), mark, ..} => {
                 self.state = State::ImplicitDocumentStart;
                 self.skip();
                 Ok(ParsedEventMarker::new(Event::StreamStart, mark))
             }
-            Token{mark, ..} => Err(ScanError::new(mark, "did not find expected <stream-start>")),
+            Token{mark, ..} => Err(ScanError::new_with_kind(mark, ScanErrorKind::UnexpectedToken, "did not find expected <stream-start>")),
         }
     }
 
@@ -359,7 +711,7 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
         }
 
         match *self.peek_token()? {
-            Token{tokentype: TokenType::StreamEnd, mark} => {
+            Token{tokentype: TokenType::StreamEnd, mark, ..} => {
                 self.state = State::End;
                 self.skip();
                 Ok(ParsedEventMarker::new(Event::StreamEnd, mark))
@@ -374,7 +726,7 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
                 self.parser_process_directives()?;
                 self.push_state(State::DocumentEnd);
                 self.state = State::BlockNode;
-                Ok(ParsedEventMarker::new(Event::DocumentStart, mark))
+                self.new_document(mark, mark)
             }
             _ => {
                 // explicit document
@@ -384,49 +736,133 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
     }
 
     fn parser_process_directives(&mut self) -> Result<(), ScanError> {
+        let mut tag_handles = HashMap::new();
+        self.version = None;
         loop {
             match self.peek_token()?.tokentype {
-                TokenType::VersionDirective(_, _) => {
-                    // XXX parsing with warning according to spec
-                    //if major != 1 || minor > 2 {
-                    //    return Err(ScanError::new(tok.0,
-                    //        "found incompatible YAML document"));
-                    //}
+                TokenType::VersionDirective(major, minor) => {
+                    let mark = self.peek_token()?.mark;
+                    if self.version.is_some() {
+                        return Err(ScanError::new_with_kind(
+                            mark,
+                            ScanErrorKind::InvalidDirective,
+                            "while parsing a document, found duplicate %YAML directive",
+                        ));
+                    }
+                    if major != 1 {
+                        return Err(ScanError::new_with_kind(
+                            mark,
+                            ScanErrorKind::UnsupportedVersion,
+                            &format!(
+                                "while parsing a document, found incompatible YAML version {}.{}",
+                                major, minor
+                            ),
+                        ));
+                    }
+                    if minor > 2 {
+                        self.warnings.push(ScanError::new_with_kind(
+                            mark,
+                            ScanErrorKind::UnsupportedVersion,
+                            &format!(
+                                "while parsing a document, found YAML version {}.{} newer than the supported 1.2",
+                                major, minor
+                            ),
+                        ));
+                    }
+                    self.version = Some((major, minor));
                 }
                 TokenType::TagDirective(..) => {
-                    // TODO add tag directive
+                    if let Token{tokentype: TokenType::TagDirective(handle, prefix), mark, ..} = self.fetch_token() {
+                        // unknown directives are scanned as an empty TagDirective
+                        if handle.is_empty() {
+                            continue;
+                        }
+                        if tag_handles.contains_key(&handle) {
+                            return Err(ScanError::new_with_kind(
+                                mark,
+                                ScanErrorKind::InvalidDirective,
+                                "while parsing a document, found duplicate %TAG directive",
+                            ));
+                        }
+                        tag_handles.insert(handle, prefix);
+                    } else {
+                        unreachable!()
+                    }
+                    continue;
                 }
                 _ => break,
             }
             self.skip();
         }
-        // TODO tag directive
+        tag_handles
+            .entry("!".to_owned())
+            .or_insert_with(|| "!".to_owned());
+        tag_handles
+            .entry("!!".to_owned())
+            .or_insert_with(|| CORE_TAG_PREFIX.to_owned());
+        self.tag_handles = tag_handles;
         Ok(())
     }
 
+    fn resolve_tag(&self, handle: String, suffix: String, mark: Marker) -> Result<Tag, ScanError> {
+        if handle.is_empty() {
+            // verbatim, or the non-specific tag `!`, see Scanner::scan_tag
+            if suffix == "!" {
+                return Ok(Tag { handle: "!".to_owned(), suffix: String::new() });
+            }
+            return Ok(Tag::from_uri(&suffix));
+        }
+        match self.tag_handles.get(&handle) {
+            Some(prefix) => Ok(Tag::from_uri(&format!("{}{}", prefix, suffix))),
+            None => Err(ScanError::new_with_kind(
+                mark,
+                ScanErrorKind::UnknownTagHandle,
+                "while parsing a node, found undefined tag handle",
+            )),
+        }
+    }
+
     fn _explict_document_start(&mut self) -> ParseResult {
         self.parser_process_directives()?;
+        let kind = unexpected_token_kind(&self.peek_token()?.tokentype);
         match *self.peek_token()? {
-            Token{tokentype: TokenType::DocumentStart, mark} => {
+            Token{tokentype: TokenType::DocumentStart, mark, end_mark} => {
                 self.push_state(State::DocumentEnd);
                 self.state = State::DocumentContent;
                 self.skip();
-                Ok(ParsedEventMarker::new(Event::DocumentStart, mark))
+                self.new_document(mark, end_mark)
             }
-            Token{mark, ..} => Err(ScanError::new(
+            Token{mark, ..} => Err(ScanError::new_with_kind(
                 mark,
+                kind,
                 "did not find expected <document start>",
             )),
         }
     }
 
+    fn new_document(&mut self, mark: Marker, end_mark: Marker) -> ParseResult {
+        if self.documents > 0 && !self.settings.is_multi_doc_allowed() {
+            return Err(ScanError::new_with_kind(
+                mark,
+                ScanErrorKind::MultipleDocuments,
+                "while parsing a stream, found a second document but multiple documents are not allowed",
+            ));
+        }
+        self.documents += 1;
+        Ok(ParsedEventMarker::new_with_end(
+            Event::DocumentStart(self.version.take()),
+            mark,
+            end_mark,
+        ))
+    }
+
     fn document_content(&mut self) -> ParseResult {
         match *self.peek_token()? {
-            Token{tokentype: TokenType::VersionDirective(..), mark}
-            | Token{tokentype: TokenType::TagDirective(..), mark}
-            | Token{tokentype: TokenType::DocumentStart, mark}
-            | Token{tokentype: TokenType::DocumentEnd, mark}
-            | Token{tokentype: TokenType::StreamEnd, mark} => {
+            Token{tokentype: TokenType::VersionDirective(..), mark, ..}
+            | Token{tokentype: TokenType::TagDirective(..), mark, ..}
+            | Token{tokentype: TokenType::DocumentStart, mark, ..}
+            | Token{tokentype: TokenType::DocumentEnd, mark, ..}
+            | Token{tokentype: TokenType::StreamEnd, mark, ..} => {
                 self.pop_state();
                 // empty scalar
                 Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
@@ -437,18 +873,18 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
 
     fn document_end(&mut self) -> ParseResult {
         let mut _implicit = true;
-        let marker: Marker = match *self.peek_token()? {
-            Token{tokentype: TokenType::DocumentEnd, mark} => {
+        let (marker, end_marker) = match *self.peek_token()? {
+            Token{tokentype: TokenType::DocumentEnd, mark, end_mark} => {
                 self.skip();
                 _implicit = false;
-                mark
+                (mark, end_mark)
             }
-            Token{mark, ..} => mark,
+            Token{mark, ..} => (mark, mark),
         };
 
         // TODO tag handling
         self.state = State::DocumentStart;
-        Ok(ParsedEventMarker::new(Event::DocumentEnd, marker))
+        Ok(ParsedEventMarker::new_with_end(Event::DocumentEnd, marker, end_marker))
     }
 
     fn register_anchor(&mut self, name: String, _: &Marker) -> Result<AnchorId, ScanError> {
@@ -468,27 +904,29 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
         let mut tag = None;
         match *self.peek_token()? {
             Token{tokentype: TokenType::Alias(_), ..} => {
-                self.pop_state();
-                if let Token{tokentype: TokenType::Alias(name), mark} = self.fetch_token() {
-                    match self.anchors.get(&name) {
+                if let Token{tokentype: TokenType::Alias(name), mark, end_mark} = self.fetch_token() {
+                    let id = match self.anchors.get(&name) {
                         None => {
-                            return Err(ScanError::new(
+                            return Err(ScanError::new_with_kind(
                                 mark,
+                                ScanErrorKind::UnknownAnchor,
                                 "while parsing node, found unknown anchor",
                             ))
                         }
-                        Some(id) => return Ok(ParsedEventMarker::new(Event::Alias(*id), mark)),
-                    }
+                        Some(&id) => id,
+                    };
+                    self.pop_state();
+                    return Ok(ParsedEventMarker::new_with_end(Event::Alias(id), mark, end_mark));
                 } else {
                     unreachable!()
                 }
             }
             Token{tokentype: TokenType::Anchor(_), ..} => {
-                if let Token{mark, tokentype: TokenType::Anchor(name)} = self.fetch_token() {
+                if let Token{mark, tokentype: TokenType::Anchor(name), ..} = self.fetch_token() {
                     anchor = Some(self.register_anchor(name, &mark)?);
                     if let TokenType::Tag(..) = self.peek_token()?.tokentype {
-                        if let tg @ TokenType::Tag(..) = self.fetch_token().tokentype {
-                            tag = Some(tg);
+                        if let Token{tokentype: TokenType::Tag(handle, suffix), mark, ..} = self.fetch_token() {
+                            tag = Some(self.resolve_tag(handle, suffix, mark)?);
                         } else {
                             unreachable!()
                         }
@@ -498,10 +936,10 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
                 }
             }
             Token{tokentype: TokenType::Tag(..), ..} => {
-                if let tg @ TokenType::Tag(..) = self.fetch_token().tokentype {
-                    tag = Some(tg);
+                if let Token{tokentype: TokenType::Tag(handle, suffix), mark, ..} = self.fetch_token() {
+                    tag = Some(self.resolve_tag(handle, suffix, mark)?);
                     if let TokenType::Anchor(_) = self.peek_token()?.tokentype {
-                        if let Token{tokentype: TokenType::Anchor(name), mark} = self.fetch_token() {
+                        if let Token{tokentype: TokenType::Anchor(name), mark, ..} = self.fetch_token() {
                             anchor = Some(self.register_anchor(name, &mark)?);
                         } else {
                             unreachable!()
@@ -513,42 +951,47 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
             }
             _ => {}
         }
+        let kind = unexpected_token_kind(&self.peek_token()?.tokentype);
         match *self.peek_token()? {
-            Token{tokentype: TokenType::BlockEntry, mark} if indentless_sequence => {
+            Token{tokentype: TokenType::BlockEntry, mark, ..} if indentless_sequence => {
+                self.blocks.push((self.states.len(), mark.col(), true));
                 self.state = State::IndentlessSequenceEntry;
-                Ok(ParsedEventMarker::new(Event::SequenceStart(anchor), mark))
+                Ok(ParsedEventMarker::new(Event::SequenceStart(anchor, tag), mark))
             }
             Token{tokentype: TokenType::Scalar(..), ..} => {
                 self.pop_state();
-                if let Token{tokentype: TokenType::Scalar(style, value), mark} = self.fetch_token() {
-                    Ok(ParsedEventMarker::new(Event::Scalar{value, style, anchor, tag}, mark))
+                if let Token{tokentype: TokenType::Scalar(style, value), mark, end_mark} = self.fetch_token() {
+                    Ok(ParsedEventMarker::new_with_end(Event::Scalar{value, style, anchor, tag}, mark, end_mark))
                 } else {
                     unreachable!()
                 }
             }
-            Token{tokentype: TokenType::FlowSequenceStart, mark} => {
+            Token{tokentype: TokenType::FlowSequenceStart, mark, end_mark} => {
                 self.state = State::FlowSequenceFirstEntry;
-                Ok(ParsedEventMarker::new(Event::SequenceStart(anchor), mark))
+                Ok(ParsedEventMarker::new_with_end(Event::SequenceStart(anchor, tag), mark, end_mark))
             }
-            Token{tokentype: TokenType::FlowMappingStart, mark} => {
+            Token{tokentype: TokenType::FlowMappingStart, mark, end_mark} => {
                 self.state = State::FlowMappingFirstKey;
-                Ok(ParsedEventMarker::new(Event::MappingStart(anchor), mark))
+                Ok(ParsedEventMarker::new_with_end(Event::MappingStart(anchor, tag), mark, end_mark))
             }
-            Token{tokentype: TokenType::BlockSequenceStart, mark} if block => {
+            Token{tokentype: TokenType::BlockSequenceStart, mark, ..} if block => {
+                self.blocks.push((self.states.len(), mark.col(), false));
                 self.state = State::BlockSequenceFirstEntry;
-                Ok(ParsedEventMarker::new(Event::SequenceStart(anchor), mark))
+                Ok(ParsedEventMarker::new(Event::SequenceStart(anchor, tag), mark))
             }
-            Token{tokentype: TokenType::BlockMappingStart, mark} if block => {
+            Token{tokentype: TokenType::BlockMappingStart, mark, ..} if block => {
+                self.blocks.push((self.states.len(), mark.col(), false));
                 self.state = State::BlockMappingFirstKey;
-                Ok(ParsedEventMarker::new(Event::MappingStart(anchor), mark))
+                Ok(ParsedEventMarker::new(Event::MappingStart(anchor, tag), mark))
             }
             // ex 7.2, an empty scalar can follow a secondary tag
             Token{mark, ..} if tag.is_some() || anchor.is_some() => {
                 self.pop_state();
                 Ok(ParsedEventMarker::new(Event::empty_scalar_with_anchor(anchor, tag), mark))
             }
-            Token{mark, ..} => Err(ScanError::new(
+            Token{mark, ..} => Err(ScanError::new_with_kind(
                 mark,
+                kind,
                 "while parsing a node, did not find expected node content",
             )),
         }
@@ -565,9 +1008,9 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
             Token{tokentype: TokenType::Key, ..} => {
                 self.skip();
                 match *self.peek_token()? {
-                    Token{tokentype: TokenType::Key, mark}
-                    | Token{tokentype: TokenType::Value, mark}
-                    | Token{tokentype: TokenType::BlockEnd, mark} => {
+                    Token{tokentype: TokenType::Key, mark, ..}
+                    | Token{tokentype: TokenType::Value, mark, ..}
+                    | Token{tokentype: TokenType::BlockEnd, mark, ..} => {
                         self.state = State::BlockMappingValue;
                         // empty scalar
                         Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
@@ -579,17 +1022,19 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
                 }
             }
             // XXX(chenyh): libyaml failed to parse spec 1.2, ex8.18
-            Token{tokentype: TokenType::Value, mark} => {
+            Token{tokentype: TokenType::Value, mark, ..} => {
                 self.state = State::BlockMappingValue;
                 Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
             }
-            Token{tokentype: TokenType::BlockEnd, mark} => {
+            Token{tokentype: TokenType::BlockEnd, ..} => {
                 self.pop_state();
+                self.blocks.pop();
                 self.skip();
-                Ok(ParsedEventMarker::new(Event::MappingEnd, mark))
+                Ok(ParsedEventMarker::new(Event::MappingEnd, self.last_end))
             }
-            Token{mark, ..} => Err(ScanError::new(
+            Token{mark, ..} => Err(ScanError::new_with_kind(
                 mark,
+                self.block_error_kind(mark),
                 "while parsing a block mapping, did not find expected key",
             )),
         }
@@ -600,9 +1045,9 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
             Token{tokentype: TokenType::Value, ..} => {
                 self.skip();
                 match *self.peek_token()? {
-                    Token{tokentype: TokenType::Key, mark}
-                    | Token{tokentype: TokenType::Value, mark}
-                    | Token{tokentype: TokenType::BlockEnd, mark} => {
+                    Token{tokentype: TokenType::Key, mark, ..}
+                    | Token{tokentype: TokenType::Value, mark, ..}
+                    | Token{tokentype: TokenType::BlockEnd, mark, ..} => {
                         self.state = State::BlockMappingKey;
                         // empty scalar
                         Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
@@ -623,57 +1068,60 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
 
     fn flow_mapping_key(&mut self, first: bool) -> ParseResult {
         if first {
-            let _ = self.peek_token()?;
+            let Token{mark, ..} = *self.peek_token()?;
+            self.marks.push((mark, ContextKind::FlowMapping));
             self.skip();
         }
-        let marker: Marker =
-            {
-                match *self.peek_token()? {
-                    Token{tokentype: TokenType::FlowMappingEnd, mark} => mark,
-                    Token{mark, ..} => {
-                        if !first {
-                            match *self.peek_token()? {
-                            Token{tokentype: TokenType::FlowEntry, ..} => self.skip(),
-                            Token{mark, ..} => return Err(ScanError::new(mark,
-                                "while parsing a flow mapping, did not find expected ',' or '}'"))
-                        }
-                        }
+        match *self.peek_token()? {
+            Token{tokentype: TokenType::FlowMappingEnd, ..} => (),
+            _ => {
+                if !first {
+                    let kind = unexpected_token_kind(&self.peek_token()?.tokentype);
+                    let context = *self.marks.last().unwrap();
+                    match *self.peek_token()? {
+                        Token{tokentype: TokenType::FlowEntry, ..} => self.skip(),
+                        Token{mark, ..} => return Err(ScanError::new_with_context(mark,
+                            kind,
+                            "while parsing a flow mapping, did not find expected ',' or '}'",
+                            context.0,
+                            context.1))
+                    }
+                }
 
+                match *self.peek_token()? {
+                    Token{tokentype: TokenType::Key, ..} => {
+                        self.skip();
                         match *self.peek_token()? {
-                            Token{tokentype: TokenType::Key, ..} => {
-                                self.skip();
-                                match *self.peek_token()? {
-                                    Token{tokentype: TokenType::Value, mark}
-                                    | Token{tokentype: TokenType::FlowEntry, mark}
-                                    | Token{tokentype: TokenType::FlowMappingEnd, mark} => {
-                                        self.state = State::FlowMappingValue;
-                                        return Ok(ParsedEventMarker::new(Event::empty_scalar(), mark));
-                                    }
-                                    _ => {
-                                        self.push_state(State::FlowMappingValue);
-                                        return self.parse_node(false, false);
-                                    }
-                                }
-                            }
-                            Token{tokentype: TokenType::Value, mark} => {
+                            Token{tokentype: TokenType::Value, mark, ..}
+                            | Token{tokentype: TokenType::FlowEntry, mark, ..}
+                            | Token{tokentype: TokenType::FlowMappingEnd, mark, ..} => {
                                 self.state = State::FlowMappingValue;
                                 return Ok(ParsedEventMarker::new(Event::empty_scalar(), mark));
                             }
-                            Token{tokentype: TokenType::FlowMappingEnd, ..} => (),
                             _ => {
-                                self.push_state(State::FlowMappingEmptyValue);
+                                self.push_state(State::FlowMappingValue);
                                 return self.parse_node(false, false);
                             }
                         }
-
-                        mark
+                    }
+                    Token{tokentype: TokenType::Value, mark, ..} => {
+                        self.state = State::FlowMappingValue;
+                        return Ok(ParsedEventMarker::new(Event::empty_scalar(), mark));
+                    }
+                    Token{tokentype: TokenType::FlowMappingEnd, ..} => (),
+                    _ => {
+                        self.push_state(State::FlowMappingEmptyValue);
+                        return self.parse_node(false, false);
                     }
                 }
-            };
+            }
+        }
 
+        let Token{mark, end_mark, ..} = *self.peek_token()?;
         self.pop_state();
+        self.marks.pop();
         self.skip();
-        Ok(ParsedEventMarker::new(Event::MappingEnd, marker))
+        Ok(ParsedEventMarker::new_with_end(Event::MappingEnd, mark, end_mark))
     }
 
     fn flow_mapping_value(&mut self, empty: bool) -> ParseResult {
@@ -684,7 +1132,7 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
                 return Ok(ParsedEventMarker::new(Event::empty_scalar(), mark));
             } else {
                 match *self.peek_token()? {
-                    Token{tokentype: TokenType::Value, mark} => {
+                    Token{tokentype: TokenType::Value, mark, ..} => {
                         self.skip();
                         match self.peek_token()?.tokentype {
                             TokenType::FlowEntry | TokenType::FlowMappingEnd => {}
@@ -705,39 +1153,46 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
     }
 
     fn flow_sequence_entry(&mut self, first: bool) -> ParseResult {
-        // skip FlowMappingStart
+        // skip FlowSequenceStart
         if first {
-            let _ = self.peek_token()?;
-            //self.marks.push(tok.0);
+            let Token{mark, ..} = *self.peek_token()?;
+            self.marks.push((mark, ContextKind::FlowSequence));
             self.skip();
         }
+        let kind = unexpected_token_kind(&self.peek_token()?.tokentype);
+        let context = *self.marks.last().unwrap();
         match *self.peek_token()? {
-            Token{tokentype: TokenType::FlowSequenceEnd, mark} => {
+            Token{tokentype: TokenType::FlowSequenceEnd, mark, end_mark} => {
                 self.pop_state();
+                self.marks.pop();
                 self.skip();
-                return Ok(ParsedEventMarker::new(Event::SequenceEnd, mark));
+                return Ok(ParsedEventMarker::new_with_end(Event::SequenceEnd, mark, end_mark));
             }
             Token{tokentype: TokenType::FlowEntry, ..} if !first => {
                 self.skip();
             }
             Token{mark, ..} if !first => {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_context(
                     mark,
+                    kind,
                     "while parsing a flow sequence, expectd ',' or ']'",
+                    context.0,
+                    context.1,
                 ));
             }
             _ => { /* next */ }
         }
         match *self.peek_token()? {
-            Token{tokentype: TokenType::FlowSequenceEnd, mark} => {
+            Token{tokentype: TokenType::FlowSequenceEnd, mark, end_mark} => {
                 self.pop_state();
+                self.marks.pop();
                 self.skip();
-                Ok(ParsedEventMarker::new(Event::SequenceEnd, mark))
+                Ok(ParsedEventMarker::new_with_end(Event::SequenceEnd, mark, end_mark))
             }
-            Token{tokentype: TokenType::Key, mark} => {
+            Token{tokentype: TokenType::Key, mark, ..} => {
                 self.state = State::FlowSequenceEntryMappingKey;
                 self.skip();
-                Ok(ParsedEventMarker::new(Event::MappingStart(None), mark))
+                Ok(ParsedEventMarker::new(Event::MappingStart(None, None), mark))
             }
             _ => {
                 self.push_state(State::FlowSequenceEntry);
@@ -749,17 +1204,18 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
     fn indentless_sequence_entry(&mut self) -> ParseResult {
         match *self.peek_token()? {
             Token{tokentype: TokenType::BlockEntry, ..} => (),
-            Token{mark, ..} => {
+            _ => {
                 self.pop_state();
-                return Ok(ParsedEventMarker::new(Event::SequenceEnd, mark));
+                self.blocks.pop();
+                return Ok(ParsedEventMarker::new(Event::SequenceEnd, self.last_end));
             }
         }
         self.skip();
         match *self.peek_token()? {
-            Token{tokentype: TokenType::BlockEntry, mark}
-            | Token{tokentype: TokenType::Key, mark}
-            | Token{tokentype: TokenType::Value, mark}
-            | Token{tokentype: TokenType::BlockEnd, mark} => {
+            Token{tokentype: TokenType::BlockEntry, mark, ..}
+            | Token{tokentype: TokenType::Key, mark, ..}
+            | Token{tokentype: TokenType::Value, mark, ..}
+            | Token{tokentype: TokenType::BlockEnd, mark, ..} => {
                 self.state = State::IndentlessSequenceEntry;
                 Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
             }
@@ -778,15 +1234,16 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
             self.skip();
         }
         match *self.peek_token()? {
-            Token{tokentype: TokenType::BlockEnd, mark} => {
+            Token{tokentype: TokenType::BlockEnd, ..} => {
                 self.pop_state();
+                self.blocks.pop();
                 self.skip();
-                Ok(ParsedEventMarker::new(Event::SequenceEnd, mark))
+                Ok(ParsedEventMarker::new(Event::SequenceEnd, self.last_end))
             }
             Token{tokentype: TokenType::BlockEntry, ..} => {
                 self.skip();
                 match *self.peek_token()? {
-                    Token{tokentype: TokenType::BlockEntry, mark} | Token{tokentype: TokenType::BlockEnd, mark} => {
+                    Token{tokentype: TokenType::BlockEntry, mark, ..} | Token{tokentype: TokenType::BlockEnd, mark, ..} => {
                         self.state = State::BlockSequenceEntry;
                         Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
                     }
@@ -796,8 +1253,9 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
                     }
                 }
             }
-            Token{mark, ..} => Err(ScanError::new(
+            Token{mark, ..} => Err(ScanError::new_with_kind(
                 mark,
+                self.block_error_kind(mark),
                 "while parsing a block collection, did not find expected '-' indicator",
             )),
         }
@@ -805,9 +1263,9 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
 
     fn flow_sequence_entry_mapping_key(&mut self) -> ParseResult {
         match *self.peek_token()? {
-            Token{tokentype: TokenType::Value, mark}
-            | Token{tokentype: TokenType::FlowEntry, mark}
-            | Token{tokentype: TokenType::FlowSequenceEnd, mark} => {
+            Token{tokentype: TokenType::Value, mark, ..}
+            | Token{tokentype: TokenType::FlowEntry, mark, ..}
+            | Token{tokentype: TokenType::FlowSequenceEnd, mark, ..} => {
                 self.skip();
                 self.state = State::FlowSequenceEntryMappingValue;
                 Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
@@ -825,7 +1283,7 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
                 self.skip();
                 self.state = State::FlowSequenceEntryMappingValue;
                 match *self.peek_token()? {
-                    Token{tokentype: TokenType::FlowEntry, mark} | Token{tokentype: TokenType::FlowSequenceEnd, mark} => {
+                    Token{tokentype: TokenType::FlowEntry, mark, ..} | Token{tokentype: TokenType::FlowSequenceEnd, mark, ..} => {
                         self.state = State::FlowSequenceEntryMappingEnd;
                         Ok(ParsedEventMarker::new(Event::empty_scalar(), mark))
                     }
@@ -844,15 +1302,68 @@ impl<TR: Iterator<Item = char>, TS: YamlSettings> Parser<TR, TS> {
 
     fn flow_sequence_entry_mapping_end(&mut self) -> ParseResult {
         self.state = State::FlowSequenceEntry;
-        Ok(ParsedEventMarker::new(Event::MappingEnd, self.scanner.mark()))
+        Ok(ParsedEventMarker::new(Event::MappingEnd, self.last_end))
+    }
+}
+
+/// Pull events one at a time, e.g. to stop reading early. The iterator ends
+/// after `Event::StreamEnd` or the first error.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::Event;
+/// use yaml_rust::parser::Parser;
+/// use yaml_rust::settings::YamlStandardSettings;
+///
+/// let settings = YamlStandardSettings::new();
+/// let parser = Parser::new("first: 1\nsecond: 2\n".chars(), &settings);
+/// let first_key = parser
+///     .filter_map(|ev| match ev {
+///         Ok((Event::Scalar{value, ..}, _)) => Some(value),
+///         _ => None,
+///     })
+///     .next();
+/// assert_eq!(first_key.unwrap(), "first");
+/// ```
+impl<TR: Iterator<Item = char>, TS: YamlSettings> Iterator for Parser<TR, TS> {
+    type Item = Result<(Event, Marker), ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == State::End && self.current.is_none() {
+            return None;
+        }
+        match self.next_event() {
+            Ok(ParsedEventMarker{event, mark, ..}) => Some(Ok((event, mark))),
+            Err(e) => {
+                self.state = State::End;
+                Some(Err(e))
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Event, Parser};
+    use super::{Event, Parser, Tag};
+    use scanner::{ScanError, ScanErrorKind};
     use settings::YamlStandardSettings;
 
+    fn collect_tags(s: &str) -> Result<Vec<Tag>, ScanError> {
+        let settings = YamlStandardSettings::new();
+        let mut p = Parser::new(s.chars(), &settings);
+        let mut tags = Vec::new();
+        loop {
+            match p.next_event()?.event {
+                Event::Scalar{tag: Some(tag), ..}
+                | Event::SequenceStart(_, Some(tag))
+                | Event::MappingStart(_, Some(tag)) => tags.push(tag),
+                Event::StreamEnd => return Ok(tags),
+                _ => {}
+            }
+        }
+    }
+
     #[test]
     fn test_peek_eq_parse() {
         let s = "
@@ -871,9 +1382,186 @@ a5: *x
         let mut p = Parser::new(s.chars(), &settings);
         while {
             let event_peek = p.peek().unwrap().clone();
-            let event = p.next().unwrap();
+            let event = p.next_event().unwrap();
             assert_eq!(event, event_peek);
             event.event != Event::StreamEnd
         } {}
     }
+
+    #[test]
+    fn test_tag_directives() {
+        let s = "
+%TAG !e! tag:example.com,2000:app/
+%TAG ! !local-
+---
+- !e!foo [a]
+- !bar {b: c}
+- !!str d
+- !<tag:example.com,2000:app/foo> e
+- ! f
+--- !!map
+g: !e h
+";
+        let tags = collect_tags(s).unwrap();
+        let uris: Vec<String> = tags.iter().map(Tag::uri).collect();
+        assert_eq!(
+            uris,
+            vec![
+                "tag:example.com,2000:app/foo",
+                "!local-bar",
+                "tag:yaml.org,2002:str",
+                "tag:example.com,2000:app/foo",
+                "!",
+                "tag:yaml.org,2002:map",
+                "!e",
+            ]
+        );
+        assert_eq!(tags[0], tags[3]);
+        assert_eq!(tags[2].to_string(), "!!str");
+        assert_eq!(tags[1].to_string(), "!local-bar");
+        assert_eq!(tags[0].to_string(), "!<tag:example.com,2000:app/foo>");
+    }
+
+    #[test]
+    fn test_undefined_tag_handle() {
+        let err = collect_tags("- !e!foo bar").unwrap_err();
+        assert_eq!(err.marker().line(), 1);
+        assert_eq!(err.marker().col(), 2);
+
+        // directives only apply to the next document
+        let s = "%TAG !e! tag:example.com,2000:\n--- !e!foo bar\n--- !e!foo bar\n";
+        let err = collect_tags(s).unwrap_err();
+        assert_eq!(err.marker().line(), 3);
+
+        let s = "%TAG !e! tag:a:\n%TAG !e! tag:b:\n--- a\n";
+        let err = collect_tags(s).unwrap_err();
+        assert_eq!(err.marker().line(), 2);
+    }
+
+    #[test]
+    fn test_version_directive() {
+        let s = "%YAML 1.1\n--- a\n...\n--- b\n...\n%YAML 1.3\n--- c\n";
+        let settings = YamlStandardSettings::new();
+        let mut p = Parser::new(s.chars(), &settings);
+        let mut versions = Vec::new();
+        loop {
+            match p.next_event().unwrap().event {
+                Event::DocumentStart(version) => versions.push(version),
+                Event::StreamEnd => break,
+                _ => {}
+            }
+        }
+        assert_eq!(versions, vec![Some((1, 1)), None, Some((1, 3))]);
+        let warnings = p.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].marker().line(), 6);
+
+        let mut p = Parser::new("%YAML 2.0\n--- a\n".chars(), &settings);
+        p.next_event().unwrap();
+        assert_eq!(p.next_event().unwrap_err().marker().line(), 1);
+
+        let mut p = Parser::new("%YAML 1.2\n%YAML 1.2\n--- a\n".chars(), &settings);
+        p.next_event().unwrap();
+        assert_eq!(p.next_event().unwrap_err().marker().line(), 2);
+    }
+
+    #[test]
+    fn test_event_spans() {
+        let s = "a: [b, {c: d}]\ne:\n  - >\n    f\n    g\n--- x\n...\n";
+        let settings = YamlStandardSettings::new();
+        let mut p = Parser::new(s.chars(), &settings);
+        let mut spans = Vec::new();
+        loop {
+            let ev = p.next_event().unwrap();
+            spans.push((
+                (ev.mark.line(), ev.mark.col()),
+                (ev.end_mark.line(), ev.end_mark.col()),
+            ));
+            if ev.event == Event::StreamEnd {
+                break;
+            }
+        }
+        assert_eq!(
+            spans,
+            vec![
+                ((1, 0), (1, 0)),   // StreamStart
+                ((1, 0), (1, 0)),   // DocumentStart
+                ((1, 0), (1, 0)),   // MappingStart
+                ((1, 0), (1, 1)),   // a
+                ((1, 3), (1, 4)),   // SequenceStart
+                ((1, 4), (1, 5)),   // b
+                ((1, 7), (1, 8)),   // MappingStart
+                ((1, 8), (1, 9)),   // c
+                ((1, 11), (1, 12)), // d
+                ((1, 12), (1, 13)), // MappingEnd
+                ((1, 13), (1, 14)), // SequenceEnd
+                ((2, 0), (2, 1)),   // e
+                ((3, 2), (3, 2)),   // SequenceStart
+                ((3, 4), (5, 5)),   // >
+                ((5, 5), (5, 5)),   // SequenceEnd
+                ((5, 5), (5, 5)),   // MappingEnd
+                ((6, 0), (6, 0)),   // DocumentEnd
+                ((6, 0), (6, 3)),   // DocumentStart
+                ((6, 4), (6, 5)),   // x
+                ((7, 0), (7, 3)),   // DocumentEnd
+                ((8, 0), (8, 0)),   // StreamEnd
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iterator() {
+        let settings = YamlStandardSettings::new();
+        let p = Parser::new("[a, b]".chars(), &settings);
+        let events: Vec<_> = p.map(|ev| ev.unwrap().0).collect();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[0], Event::StreamStart);
+        assert_eq!(events[7], Event::StreamEnd);
+
+        // the iterator continues after peeking
+        let mut p = Parser::new("a".chars(), &settings);
+        assert_eq!(*p.peek().unwrap().event(), Event::StreamStart);
+        let (event, mark) = Iterator::next(&mut p).unwrap().unwrap();
+        assert_eq!(event, Event::StreamStart);
+        assert_eq!(mark.index(), 0);
+        assert_eq!(p.count(), 4);
+
+        // errors end the iteration
+        let mut p = Parser::new("[a, b".chars(), &settings);
+        let events: Vec<_> = p.by_ref().collect();
+        assert!(events.last().unwrap().is_err());
+        assert!(events[..events.len() - 1].iter().all(|ev| ev.is_ok()));
+        assert!(Iterator::next(&mut p).is_none());
+    }
+
+    #[test]
+    fn test_recovery() {
+        let s = "- [a, &x b\n- *y\n- *x\n";
+        let settings = YamlStandardSettings::new();
+        let mut p = Parser::new(s.chars(), &settings);
+        p.set_recovery(true);
+        let mut events = Vec::new();
+        loop {
+            let ev = p.next_event().unwrap();
+            if ev.event == Event::StreamEnd {
+                break;
+            }
+            events.push((ev.event, ev.mark.index()));
+        }
+        // the broken flow sequence is dropped as a whole, so its anchor is not
+        // defined either; the scanner finds the error before the sequence starts
+        assert_eq!(
+            events[3..6].to_vec(),
+            vec![(Event::Invalid, 10), (Event::Invalid, 13), (Event::Invalid, 18)]
+        );
+        let errors: Vec<_> = p.take_errors().iter().map(|e| (e.marker().index(), e.kind())).collect();
+        assert_eq!(
+            errors,
+            vec![
+                (10, ScanErrorKind::UnexpectedEof),
+                (13, ScanErrorKind::UnknownAnchor),
+                (18, ScanErrorKind::UnknownAnchor),
+            ]
+        );
+    }
 }
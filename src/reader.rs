@@ -0,0 +1,424 @@
+use scanner::{Marker, TEncoding};
+use std::char;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::str;
+
+use scanner::ScanError;
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Errors while loading from bytes, see `loader::yaml_load_from_reader`.
+#[derive(Debug)]
+pub enum LoadError {
+    /// Reading the input failed at the given position.
+    Io(io::Error, Marker),
+    /// The input is not valid in its encoding at the given position.
+    Decode(Marker),
+    Scan(ScanError),
+}
+
+impl LoadError {
+    pub fn marker(&self) -> &Marker {
+        match *self {
+            LoadError::Io(_, ref mark) | LoadError::Decode(ref mark) => mark,
+            LoadError::Scan(ref err) => err.marker(),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io(..) => "error reading the input",
+            LoadError::Decode(..) => "invalid byte sequence in the input",
+            LoadError::Scan(..) => "error parsing the input",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            LoadError::Io(ref err, _) => Some(err),
+            LoadError::Decode(..) => None,
+            LoadError::Scan(ref err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    // col starts from 0
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err, mark) => write!(
+                formatter,
+                "while reading the input, {} at line {} column {}",
+                err,
+                mark.line(),
+                mark.col() + 1
+            ),
+            LoadError::Decode(mark) => write!(
+                formatter,
+                "while decoding the input, found an invalid byte sequence at line {} column {}",
+                mark.line(),
+                mark.col() + 1
+            ),
+            LoadError::Scan(ref err) => fmt::Display::fmt(err, formatter),
+        }
+    }
+}
+
+impl From<ScanError> for LoadError {
+    fn from(err: ScanError) -> Self {
+        LoadError::Scan(err)
+    }
+}
+
+// a failure which is reported once the characters before it are consumed
+#[derive(Debug)]
+enum Failure {
+    Io(io::Error),
+    Decode,
+}
+
+/// Detect the encoding from the first bytes of the input, as described in
+/// section 5.2 of the YAML 1.2 spec.
+fn detect_encoding(bytes: &[u8]) -> TEncoding {
+    let byte = |i: usize| bytes.get(i).cloned();
+    match (byte(0), byte(1), byte(2), byte(3)) {
+        (Some(0), Some(0), Some(0xFE), Some(0xFF)) | (Some(0), Some(0), Some(0), Some(_)) => TEncoding::Utf32Be,
+        (Some(0xFF), Some(0xFE), Some(0), Some(0)) | (Some(_), Some(0), Some(0), Some(0)) => TEncoding::Utf32Le,
+        (Some(0xFE), Some(0xFF), _, _) | (Some(0), Some(_), _, _) => TEncoding::Utf16Be,
+        (Some(0xFF), Some(0xFE), _, _) | (Some(_), Some(0), _, _) => TEncoding::Utf16Le,
+        _ => TEncoding::Utf8,
+    }
+}
+
+// whether `bytes` is the start of a multi-byte UTF-8 sequence
+fn is_incomplete_utf8(bytes: &[u8]) -> bool {
+    let len = match bytes.first() {
+        Some(&b) if b >= 0xC2 && b <= 0xDF => 2,
+        Some(&b) if b >= 0xE0 && b <= 0xEF => 3,
+        Some(&b) if b >= 0xF0 && b <= 0xF4 => 4,
+        _ => return false,
+    };
+    bytes.len() < len && bytes[1..].iter().all(|&b| b >= 0x80 && b <= 0xBF)
+}
+
+fn u16_from_le_bytes(b: &[u8]) -> u16 {
+    b[0] as u16 | (b[1] as u16) << 8
+}
+
+fn u16_from_be_bytes(b: &[u8]) -> u16 {
+    (b[0] as u16) << 8 | b[1] as u16
+}
+
+fn u32_from_le_bytes(b: &[u8]) -> u32 {
+    b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24
+}
+
+fn u32_from_be_bytes(b: &[u8]) -> u32 {
+    (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32
+}
+
+/// Decodes the bytes of a reader into characters, reading the input in
+/// chunks as the characters are consumed. The encoding is detected from the
+/// first bytes, UTF-8, UTF-16 and UTF-32 are supported.
+///
+/// The iterator stops at the first I/O or decoding error, which is then
+/// available from `take_error`.
+#[derive(Debug)]
+pub struct Decoder<R> {
+    reader: R,
+    encoding: Option<TEncoding>,
+    // undecoded bytes, e.g. an incomplete sequence at the end of a chunk
+    bytes: Vec<u8>,
+    chars: VecDeque<char>,
+    // the position of the next character
+    mark: Marker,
+    failure: Option<Failure>,
+    error: Option<LoadError>,
+    eof: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Decoder<R> {
+        Decoder {
+            reader,
+            encoding: None,
+            bytes: Vec::new(),
+            chars: VecDeque::new(),
+            mark: Marker::new(0, 1, 0),
+            failure: None,
+            error: None,
+            eof: false,
+        }
+    }
+
+    /// Take the error which stopped the decoding, if any.
+    pub fn take_error(&mut self) -> Option<LoadError> {
+        self.error.take()
+    }
+
+    /// The encoding of the input, reading the first bytes if necessary.
+    pub fn encoding(&mut self) -> TEncoding {
+        loop {
+            if let Some(encoding) = self.encoding {
+                return encoding;
+            }
+            if self.bytes.len() >= 4 || self.eof || self.failure.is_some() {
+                self.encoding = Some(detect_encoding(&self.bytes));
+            } else {
+                self.read_chunk();
+            }
+        }
+    }
+
+    fn read_chunk(&mut self) {
+        let mut chunk = [0u8; CHUNK_SIZE];
+        loop {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.bytes.extend_from_slice(&chunk[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => self.failure = Some(Failure::Io(err)),
+            }
+            return;
+        }
+    }
+
+    fn fill(&mut self) {
+        let encoding = self.encoding();
+        loop {
+            let consumed = match encoding {
+                TEncoding::Utf8 => self.decode_utf8(),
+                TEncoding::Utf16Le => self.decode_utf16(u16_from_le_bytes),
+                TEncoding::Utf16Be => self.decode_utf16(u16_from_be_bytes),
+                TEncoding::Utf32Le => self.decode_utf32(u32_from_le_bytes),
+                TEncoding::Utf32Be => self.decode_utf32(u32_from_be_bytes),
+            };
+            self.bytes.drain(..consumed);
+            if !self.chars.is_empty() || self.failure.is_some() {
+                return;
+            }
+            if self.eof {
+                if !self.bytes.is_empty() {
+                    // truncated sequence
+                    self.failure = Some(Failure::Decode);
+                }
+                return;
+            }
+            self.read_chunk();
+        }
+    }
+
+    fn decode_utf8(&mut self) -> usize {
+        match str::from_utf8(&self.bytes) {
+            Ok(s) => {
+                self.chars.extend(s.chars());
+                s.len()
+            }
+            Err(err) => {
+                let valid = err.valid_up_to();
+                self.chars
+                    .extend(str::from_utf8(&self.bytes[..valid]).unwrap().chars());
+                if !is_incomplete_utf8(&self.bytes[valid..]) {
+                    self.failure = Some(Failure::Decode);
+                }
+                valid
+            }
+        }
+    }
+
+    fn decode_utf16(&mut self, from_bytes: fn(&[u8]) -> u16) -> usize {
+        let mut units: Vec<u16> = self
+            .bytes
+            .chunks(2)
+            .filter(|b| b.len() == 2)
+            .map(from_bytes)
+            .collect();
+        // keep a leading surrogate until the rest of the pair is read
+        if !self.eof && units.last().map_or(false, |&u| u >= 0xD800 && u < 0xDC00) {
+            units.pop();
+        }
+        let mut consumed = 0;
+        for c in char::decode_utf16(units) {
+            match c {
+                Ok(c) => {
+                    consumed += c.len_utf16() * 2;
+                    self.chars.push_back(c);
+                }
+                Err(_) => {
+                    self.failure = Some(Failure::Decode);
+                    break;
+                }
+            }
+        }
+        consumed
+    }
+
+    fn decode_utf32(&mut self, from_bytes: fn(&[u8]) -> u32) -> usize {
+        let mut consumed = 0;
+        for b in self.bytes.chunks(4).filter(|b| b.len() == 4) {
+            match char::from_u32(from_bytes(b)) {
+                Some(c) => {
+                    consumed += 4;
+                    self.chars.push_back(c);
+                }
+                None => {
+                    self.failure = Some(Failure::Decode);
+                    break;
+                }
+            }
+        }
+        consumed
+    }
+}
+
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.chars.is_empty() {
+            self.fill();
+        }
+        match self.chars.pop_front() {
+            Some(c) => {
+                // count like the scanner, which skips a BOM at the start of
+                // a line without moving the column. It only allows one at the
+                // start of a document and reports any other one as an error
+                // at this same position, so that needs no tracking here
+                let (index, line, col) = (self.mark.index() + 1, self.mark.line(), self.mark.col());
+                self.mark = match c {
+                    '\n' => Marker::new(index, line + 1, 0),
+                    '\u{feff}' if col == 0 => Marker::new(index, line, col),
+                    _ => Marker::new(index, line, col + 1),
+                };
+                Some(c)
+            }
+            None => {
+                match self.failure.take() {
+                    Some(Failure::Io(err)) => self.error = Some(LoadError::Io(err, self.mark)),
+                    Some(Failure::Decode) => self.error = Some(LoadError::Decode(self.mark)),
+                    None => {}
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // hands out the input one byte at a time, then fails
+    struct SlowReader<'a> {
+        input: &'a [u8],
+        fail: bool,
+    }
+
+    impl<'a> Read for SlowReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.input.split_first() {
+                Some((b, rest)) => {
+                    buf[0] = *b;
+                    self.input = rest;
+                    Ok(1)
+                }
+                None if self.fail => Err(io::Error::new(io::ErrorKind::Other, "broken")),
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_split_sequences() {
+        let input = "a: é\nb: 日本\n";
+        let mut d = Decoder::new(SlowReader { input: input.as_bytes(), fail: false });
+        let s: String = d.by_ref().collect();
+        assert_eq!(s, input);
+        assert!(d.take_error().is_none());
+    }
+
+    fn encode(s: &str, encoding: TEncoding) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for c in s.chars() {
+            match encoding {
+                TEncoding::Utf8 => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                TEncoding::Utf16Le | TEncoding::Utf16Be => {
+                    for &u in c.encode_utf16(&mut [0; 2]).iter() {
+                        let (high, low) = ((u >> 8) as u8, u as u8);
+                        match encoding {
+                            TEncoding::Utf16Le => bytes.extend_from_slice(&[low, high]),
+                            _ => bytes.extend_from_slice(&[high, low]),
+                        }
+                    }
+                }
+                TEncoding::Utf32Le | TEncoding::Utf32Be => {
+                    let u = c as u32;
+                    let mut be = [(u >> 24) as u8, (u >> 16) as u8, (u >> 8) as u8, u as u8];
+                    if encoding == TEncoding::Utf32Le {
+                        be.reverse();
+                    }
+                    bytes.extend_from_slice(&be);
+                }
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let encodings = [
+            TEncoding::Utf8,
+            TEncoding::Utf16Le,
+            TEncoding::Utf16Be,
+            TEncoding::Utf32Le,
+            TEncoding::Utf32Be,
+        ];
+        for &encoding in &encodings {
+            for input in &["a: 😀\n", "\u{feff}a: 😀\n", "x"] {
+                let bytes = encode(input, encoding);
+                let mut d = Decoder::new(SlowReader { input: &bytes, fail: false });
+                assert_eq!(d.encoding(), encoding);
+                assert_eq!(d.by_ref().collect::<String>(), *input);
+                assert!(d.take_error().is_none());
+            }
+        }
+        assert_eq!(Decoder::new(&b""[..]).encoding(), TEncoding::Utf8);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut d = Decoder::new(&b"ab\ncd\xffef"[..]);
+        assert_eq!(d.by_ref().collect::<String>(), "ab\ncd");
+        match d.take_error() {
+            Some(LoadError::Decode(mark)) => {
+                assert_eq!((mark.index(), mark.line(), mark.col()), (5, 2, 2));
+            }
+            err => panic!("unexpected {:?}", err),
+        }
+
+        // truncated sequence at the end of the input
+        let mut d = Decoder::new(&b"ab\xc3"[..]);
+        assert_eq!(d.by_ref().collect::<String>(), "ab");
+        assert_eq!(d.take_error().unwrap().marker().col(), 2);
+
+        // lone surrogate and odd length in UTF-16
+        let mut d = Decoder::new(&b"a\x00\x00\xdcb\x00"[..]);
+        assert_eq!(d.by_ref().collect::<String>(), "a");
+        assert_eq!(d.take_error().unwrap().marker().col(), 1);
+        let mut d = Decoder::new(&b"a\x00b"[..]);
+        assert_eq!(d.by_ref().collect::<String>(), "a");
+        assert_eq!(d.take_error().unwrap().marker().col(), 1);
+
+        let mut d = Decoder::new(SlowReader { input: b"abc", fail: true });
+        assert_eq!(d.by_ref().collect::<String>(), "abc");
+        match d.take_error() {
+            Some(LoadError::Io(_, mark)) => assert_eq!(mark.col(), 3),
+            err => panic!("unexpected {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use builder::YamlStandardBuilder;
+use loader::YamlLoader;
+use scanner::ScanError;
+use settings::YamlSettings;
+use yaml::Yaml;
+
+/// Load all documents, recovering from errors instead of stopping at the
+/// first one.
+///
+/// After an error, loading resynchronizes at the next line which is not
+/// indented deeper than the block collection the error is in, or at the next
+/// document marker. The broken node becomes `Yaml::BadValue` and its key, if
+/// any, is kept. A flow collection or quoted scalar with a missing closing
+/// bracket or quote ends at such a line as well. All errors are returned
+/// ordered by their position, except that an exceeded limit of the settings
+/// still stops the loading.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::YamlStandardSettings;
+/// use yaml_rust::recovery::yaml_load_from_str_with_recovery;
+///
+/// let s = "a: 1\nb: [2, 3\nc: 4\nd: 'five\n";
+/// let (docs, errors) = yaml_load_from_str_with_recovery(s, &YamlStandardSettings::new());
+/// assert_eq!(docs[0]["a"].as_i64().unwrap(), 1);
+/// assert!(docs[0]["b"].is_badvalue());
+/// assert_eq!(docs[0]["c"].as_i64().unwrap(), 4);
+/// assert!(docs[0]["d"].is_badvalue());
+/// assert_eq!(errors.len(), 2);
+/// ```
+pub fn yaml_load_from_str_with_recovery<TS>(source: &str, settings: &TS) -> (Vec<Yaml>, Vec<ScanError>) where TS: YamlSettings {
+    let builder = YamlStandardBuilder::new(settings);
+    let mut loader = YamlLoader::new(settings, &builder);
+    let errors = loader.load_from_iter_with_recovery(source.chars());
+    (builder.into_documents(), errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::yaml_load_from_str_with_recovery;
+    use scanner::{Limit, ScanErrorKind};
+    use schema::Schema;
+    use settings::{DuplicateKeyPolicy, YamlStandardSettings};
+    use yaml::Yaml;
+
+    fn load(s: &str) -> (Vec<Yaml>, Vec<(usize, usize)>) {
+        let (docs, errors) = yaml_load_from_str_with_recovery(s, &YamlStandardSettings::new());
+        let positions = errors.iter().map(|e| (e.marker().line(), e.marker().col())).collect();
+        (docs, positions)
+    }
+
+    #[test]
+    fn test_recovery_valid() {
+        let s = "a: 1\n---\n- b\n";
+        let (docs, errors) = load(s);
+        assert_eq!(docs, ::loader::yaml_load_from_str(s).unwrap());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_recovery_nested() {
+        let s = "
+a:
+  b: {c: 1
+  d: 2
+  e:
+    - 3
+    - \"four
+    - 5
+f: 6
+";
+        let (docs, errors) = load(s);
+        let doc = &docs[0];
+        assert!(doc["a"]["b"].is_badvalue());
+        assert_eq!(doc["a"]["d"].as_i64().unwrap(), 2);
+        assert_eq!(doc["a"]["e"][0].as_i64().unwrap(), 3);
+        assert!(doc["a"]["e"][1].is_badvalue());
+        assert_eq!(doc["a"]["e"][2].as_i64().unwrap(), 5);
+        assert_eq!(doc["f"].as_i64().unwrap(), 6);
+        // the flow mapping ends at the line of `d`, the quoted scalar at the
+        // line of `5`
+        assert_eq!(errors, vec![(3, 10), (7, 6)]);
+    }
+
+    #[test]
+    fn test_recovery_markers() {
+        let s = "- [a\n- b\n\t- c\n- d\n";
+        let (docs, errors) = yaml_load_from_str_with_recovery(s, &YamlStandardSettings::new());
+        assert_eq!(docs[0], Yaml::Array(vec![Yaml::BadValue, Yaml::BadValue, Yaml::String("d".to_owned())]));
+        let indices: Vec<usize> = errors.iter().map(|e| e.marker().index()).collect();
+        // after `[a`, at `b` and at the tab
+        assert_eq!(indices, vec![4, 7, 9]);
+        assert_eq!(errors[1].marker().line(), 2);
+        assert_eq!(errors[1].marker().col(), 2);
+    }
+
+    #[test]
+    fn test_recovery_documents() {
+        let s = "a: [1\n  2: 3\n---\nb: 'x\n...\n--- c\n";
+        let (docs, errors) = load(s);
+        assert_eq!(docs.len(), 3);
+        assert!(docs[0]["a"].is_badvalue());
+        assert!(docs[1]["b"].is_badvalue());
+        assert_eq!(docs[2].as_str().unwrap(), "c");
+        assert_eq!(errors.len(), 2);
+
+        // unclosed at the end of the stream
+        let (docs, errors) = load("a: 1\nb: [2,\n  3,\n");
+        assert_eq!(docs[0]["a"].as_i64().unwrap(), 1);
+        assert!(docs[0]["b"].is_badvalue());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_recovery_loader_errors() {
+        let settings = YamlStandardSettings::new().duplicate_keys(DuplicateKeyPolicy::Error);
+        let (docs, errors) = yaml_load_from_str_with_recovery("a: 1\na: 2\nb: 3\n", &settings);
+        assert_eq!(docs[0]["a"].as_i64().unwrap(), 1);
+        assert_eq!(docs[0]["b"].as_i64().unwrap(), 3);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].marker().line(), 2);
+
+        let settings = YamlStandardSettings::new().schema(Schema::Json);
+        let (docs, errors) = yaml_load_from_str_with_recovery("- yes\n- 1\n", &settings);
+        assert!(docs[0][0].is_badvalue());
+        assert_eq!(docs[0][1].as_i64().unwrap(), 1);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_recovery_limits() {
+        let settings = YamlStandardSettings::new().max_depth(Some(2));
+        let (docs, errors) = yaml_load_from_str_with_recovery("a: [b\nc: [[[d]]]\n", &settings);
+        assert!(docs.is_empty());
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].kind(), ScanErrorKind::LimitExceeded(Limit::Depth));
+    }
+}
@@ -0,0 +1,158 @@
+use builder::YamlBuilder;
+use parser::Tag;
+use scanner::{Marker, ScanError};
+use yaml::{Bool, Float, Int, String};
+
+/// A node built with a `TagNodeBuilder`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct TagNode(usize);
+
+/// Builds the nodes a `TagResolver` resolves a scalar to. The nodes are
+/// made by the `YamlBuilder` of the loader, so they are `Yaml` or
+/// `MarkedYaml` nodes like the rest of the document, all at the position of
+/// the tagged scalar.
+pub trait TagNodeBuilder {
+    fn new_badvalue(&mut self) -> TagNode;
+    fn new_null(&mut self) -> TagNode;
+    fn new_float(&mut self, value: Float) -> TagNode;
+    fn new_int(&mut self, value: Int) -> TagNode;
+    fn new_string(&mut self, value: String) -> TagNode;
+    fn new_bool(&mut self, value: Bool) -> TagNode;
+    fn new_tagged(&mut self, tag: Tag, node: TagNode) -> TagNode;
+    fn new_sequence(&mut self, items: Vec<TagNode>) -> TagNode;
+    /// Fails like the loader for duplicate keys, depending on the
+    /// `DuplicateKeyPolicy`.
+    fn new_mapping(&mut self, entries: Vec<(TagNode, TagNode)>) -> Result<TagNode, ScanError>;
+}
+
+/// The `TagNodeBuilder` of a `YamlBuilder`, which the loader passes to the
+/// `TagResolver`s.
+pub struct BuilderTagNodes<'a, TB: YamlBuilder + 'a> {
+    builder: &'a mut TB,
+    marker: Marker,
+    nodes: Vec<TB::NodeHandle>,
+}
+
+impl<'a, TB: YamlBuilder> BuilderTagNodes<'a, TB> {
+    pub fn new(builder: &'a mut TB, marker: Marker) -> Self {
+        BuilderTagNodes {
+            builder,
+            marker,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// The handle of a node built by this builder.
+    pub fn handle(&self, node: TagNode) -> TB::NodeHandle {
+        self.nodes[node.0]
+    }
+
+    fn push(&mut self, handle: TB::NodeHandle) -> TagNode {
+        self.nodes.push(handle);
+        TagNode(self.nodes.len() - 1)
+    }
+}
+
+impl<'a, TB: YamlBuilder> TagNodeBuilder for BuilderTagNodes<'a, TB> {
+    fn new_badvalue(&mut self) -> TagNode {
+        let handle = self.builder.new_badvalue(self.marker);
+        self.push(handle)
+    }
+
+    fn new_null(&mut self) -> TagNode {
+        let handle = self.builder.new_null(self.marker);
+        self.push(handle)
+    }
+
+    fn new_float(&mut self, value: Float) -> TagNode {
+        let handle = self.builder.new_float(value, self.marker);
+        self.push(handle)
+    }
+
+    fn new_int(&mut self, value: Int) -> TagNode {
+        let handle = self.builder.new_int(value, self.marker);
+        self.push(handle)
+    }
+
+    fn new_string(&mut self, value: String) -> TagNode {
+        let handle = self.builder.new_string(value, self.marker);
+        self.push(handle)
+    }
+
+    fn new_bool(&mut self, value: Bool) -> TagNode {
+        let handle = self.builder.new_bool(value, self.marker);
+        self.push(handle)
+    }
+
+    fn new_tagged(&mut self, tag: Tag, node: TagNode) -> TagNode {
+        let node = self.handle(node);
+        let handle = self.builder.new_tagged(tag, node);
+        self.push(handle)
+    }
+
+    fn new_sequence(&mut self, items: Vec<TagNode>) -> TagNode {
+        let sequence = self.builder.new_sequence(self.marker);
+        for item in items {
+            let item = self.handle(item);
+            self.builder.add_to_sequence(sequence, item);
+        }
+        self.builder.close_sequence(sequence);
+        self.push(sequence)
+    }
+
+    fn new_mapping(&mut self, entries: Vec<(TagNode, TagNode)>) -> Result<TagNode, ScanError> {
+        let mapping = self.builder.new_mapping(self.marker);
+        for (key, item) in entries {
+            let (key, item) = (self.handle(key), self.handle(item));
+            self.builder.add_to_mapping(mapping, key, item)?;
+        }
+        self.builder.close_mapping(mapping);
+        Ok(self.push(mapping))
+    }
+}
+
+/// Resolves scalars with custom tags such as `!env` or `!duration`.
+///
+/// Resolvers are registered with `YamlStandardSettings::tag_resolver` and are
+/// tried in registration order before the built-in `!!` tags.
+///
+/// # Examples
+///
+/// ```
+/// use yaml_rust::{Tag, TagNode, TagNodeBuilder, TagResolver, YamlStandardSettings};
+/// use yaml_rust::loader::yaml_load_doc_from_str_with_settings;
+/// use yaml_rust::scanner::{Marker, ScanError};
+///
+/// struct Words;
+///
+/// impl TagResolver for Words {
+///     fn resolve(&self, tag: &Tag, value: &str, _marker: Marker, builder: &mut TagNodeBuilder)
+///         -> Option<Result<TagNode, ScanError>> {
+///         if tag.handle == "!" && tag.suffix == "words" {
+///             let words = value.split(' ').map(|w| builder.new_string(w.to_owned())).collect();
+///             Some(Ok(builder.new_sequence(words)))
+///         } else {
+///             None
+///         }
+///     }
+/// }
+///
+/// let settings = YamlStandardSettings::new().tag_resolver(Words);
+/// let doc = yaml_load_doc_from_str_with_settings("a: !words b c", &settings).unwrap();
+/// assert_eq!(doc["a"][1].as_str().unwrap(), "c");
+/// ```
+pub trait TagResolver {
+    /// Resolve the scalar `value` tagged with `tag`, found at `marker`, to a
+    /// node made with `builder`. As in `parser::Tag`, the handle of the tag
+    /// is the prefix it resolved to, e.g. `tag:yaml.org,2002:` for `!!`.
+    ///
+    /// Returns `None` if the tag is not handled by this resolver, so that the
+    /// next resolver or the default handling is used.
+    fn resolve(
+        &self,
+        tag: &Tag,
+        value: &str,
+        marker: Marker,
+        builder: &mut TagNodeBuilder,
+    ) -> Option<Result<TagNode, ScanError>>;
+}
@@ -1,10 +1,15 @@
 use std::collections::VecDeque;
 use std::error::Error;
 use std::{char, fmt};
+use settings::YamlSettings;
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub enum TEncoding {
     Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
@@ -26,7 +31,7 @@ pub struct Marker {
 }
 
 impl Marker {
-    fn new(index: usize, line: usize, col: usize) -> Marker {
+    pub fn new(index: usize, line: usize, col: usize) -> Marker {
         Marker { index, line, col }
     }
 
@@ -43,23 +48,140 @@ impl Marker {
     }
 }
 
+/// The position of the start of a stream, for use in constants.
+pub const START_MARKER: Marker = Marker { index: 0, line: 1, col: 0 };
+
+/// The kind of a `ScanError`, for telling errors apart without matching on
+/// their message.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Hash)]
+pub enum ScanErrorKind {
+    /// The stream or document ended inside a node, e.g. in a quoted scalar
+    /// or a flow collection.
+    UnexpectedEof,
+    /// A character which is not allowed at this point, e.g. `@`.
+    UnexpectedCharacter,
+    /// A token which is not allowed at this point, or a missing one, e.g. a
+    /// missing `,` in a flow collection.
+    UnexpectedToken,
+    /// A line which is not aligned with the enclosing block collection.
+    InvalidIndentation,
+    /// A tab where indentation is expected.
+    TabInIndentation,
+    /// A malformed or duplicated `%YAML` or `%TAG` directive.
+    InvalidDirective,
+    /// A `%YAML` directive with an unsupported version.
+    UnsupportedVersion,
+    /// A malformed tag.
+    InvalidTag,
+    /// A tag with a handle which is not defined by a `%TAG` directive.
+    UnknownTagHandle,
+    /// A malformed anchor or alias name.
+    InvalidAnchor,
+    /// An alias to an anchor which is not defined.
+    UnknownAnchor,
+    /// An invalid escape sequence in a double-quoted scalar.
+    InvalidEscape,
+    /// A key which already occurs in its mapping.
+    DuplicateKey,
+    /// A merge key (`<<`) whose value is not a mapping or a list of mappings.
+    InvalidMerge,
+    /// A scalar which is not allowed by the schema.
+    InvalidValue,
+    /// A second document where only one is allowed.
+    MultipleDocuments,
+    /// A limit set in the `YamlSettings` was exceeded.
+    LimitExceeded(Limit),
+    /// Any other error, e.g. one returned by a `TagResolver`.
+    Other,
+}
+
+/// The limit of a `ScanErrorKind::LimitExceeded` error.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Hash)]
+pub enum Limit {
+    /// `YamlSettings::get_max_input_chars`
+    InputChars,
+    /// `YamlSettings::get_max_scalar_length`
+    ScalarLength,
+    /// `YamlSettings::get_max_depth`, or the nesting of flow collections
+    /// the scanner can track.
+    Depth,
+    /// `YamlSettings::get_max_nodes`
+    Nodes,
+    /// `YamlSettings::get_max_documents`
+    Documents,
+    /// `YamlSettings::get_max_alias_expansion`
+    AliasExpansion,
+}
+
+/// What the context marker of a `ScanError` refers to.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Hash)]
+pub enum ContextKind {
+    /// The start of the flow sequence the error occurred in.
+    FlowSequence,
+    /// The start of the flow mapping the error occurred in.
+    FlowMapping,
+    /// The first occurrence of a duplicated key.
+    FirstDefinition,
+}
+
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct ScanError {
     mark: Marker,
+    kind: ScanErrorKind,
     info: String,
+    context: Option<(Marker, ContextKind)>,
 }
 
 impl ScanError {
     pub fn new(loc: Marker, info: &str) -> ScanError {
+        ScanError::new_with_kind(loc, ScanErrorKind::Other, info)
+    }
+
+    pub fn new_with_kind(loc: Marker, kind: ScanErrorKind, info: &str) -> ScanError {
+        ScanError {
+            mark: loc,
+            kind,
+            info: info.to_owned(),
+            context: None,
+        }
+    }
+
+    /// Creates an error which also refers to a second, related location,
+    /// e.g. the first occurrence of a duplicated key.
+    pub fn new_with_context(
+        loc: Marker,
+        kind: ScanErrorKind,
+        info: &str,
+        context_loc: Marker,
+        context_kind: ContextKind,
+    ) -> ScanError {
         ScanError {
             mark: loc,
+            kind,
             info: info.to_owned(),
+            context: Some((context_loc, context_kind)),
         }
     }
 
     pub fn marker(&self) -> &Marker {
         &self.mark
     }
+
+    pub fn kind(&self) -> ScanErrorKind {
+        self.kind
+    }
+
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+
+    pub fn context_marker(&self) -> Option<&Marker> {
+        self.context.as_ref().map(|context| &context.0)
+    }
+
+    pub fn context_kind(&self) -> Option<ContextKind> {
+        self.context.map(|context| context.1)
+    }
 }
 
 impl Error for ScanError {
@@ -117,13 +239,17 @@ pub enum TokenType {
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct Token{
     pub mark: Marker, 
+    /// The position after the last character of the token. Tokens which
+    /// do not consume any text, e.g. `BlockEnd`, end where they start.
+    pub end_mark: Marker,
     pub tokentype: TokenType,
 }
 
 impl Token {
-    fn new(mark: Marker, tokentype: TokenType) -> Self {
+    fn new(mark: Marker, end_mark: Marker, tokentype: TokenType) -> Self {
         Self {
             mark,
+            end_mark,
             tokentype,
         }
     }
@@ -166,6 +292,18 @@ pub struct Scanner<T> {
     flow_level: u8,
     tokens_parsed: usize,
     token_available: bool,
+    // a byte order mark may start the stream and each document after `...`
+    bom_allowed: bool,
+    // see `set_recovery`
+    recovery: bool,
+    // in recovery mode, the characters read of the current line
+    line: Vec<char>,
+
+    max_input_chars: Option<usize>,
+    max_scalar_length: Option<usize>,
+    chars_read: usize,
+    input_exceeded: bool,
+    encoding: TEncoding,
 }
 
 impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
@@ -251,14 +389,106 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             flow_level: 0,
             tokens_parsed: 0,
             token_available: false,
+            bom_allowed: true,
+            recovery: false,
+            line: Vec::new(),
+
+            max_input_chars: None,
+            max_scalar_length: None,
+            chars_read: 0,
+            input_exceeded: false,
+            encoding: TEncoding::Utf8,
+        }
+    }
+
+    /// Creates the YAML tokenizer which enforces the input limits of `settings`.
+    pub fn new_with_settings<TS: YamlSettings>(rdr: T, settings: &TS) -> Scanner<T> {
+        let mut scanner = Scanner::new(rdr);
+        scanner.max_input_chars = settings.get_max_input_chars();
+        scanner.max_scalar_length = settings.get_max_scalar_length();
+        scanner
+    }
+
+    /// Set the encoding the input was decoded from, which is reported in
+    /// the `StreamStart` token. The default is UTF-8.
+    pub fn set_encoding(&mut self, encoding: TEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Scan for a parser which recovers from errors, see `resync`. A flow
+    /// collection or a quoted scalar then ends with an error at a line which
+    /// is not indented deeper than the block collection around it, or which
+    /// starts a document, instead of swallowing the rest of the input when
+    /// its closing bracket or quote is missing.
+    pub fn set_recovery(&mut self, recovery: bool) {
+        self.recovery = recovery;
+    }
+
+    /// Go on scanning after the error at `error`. The input is skipped up to
+    /// the next line after the error which is not indented deeper than
+    /// `indent`, or which starts a document. Scanning goes on in the block
+    /// collections at the columns `indents`.
+    pub fn resync(&mut self, error: &Marker, indent: isize, indents: &[usize]) {
+        self.error = None;
+        self.tokens.clear();
+        self.token_available = false;
+        self.stream_end_produced = false;
+        self.flow_level = 0;
+        self.simple_keys.truncate(1);
+        for sk in &mut self.simple_keys {
+            sk.possible = false;
+        }
+        self.allow_simple_key();
+        self.indent = -1;
+        self.indents.clear();
+        for &col in indents {
+            self.indents.push(self.indent);
+            self.indent = col as isize;
+        }
+
+        // the scanner reads ahead, so the current line may come after the
+        // error and has to be read again
+        self.mark.index -= self.line.len();
+        self.mark.col = 0;
+        while let Some(c) = self.line.pop() {
+            self.buffer.push_front(c);
+        }
+
+        let mut skip = error.line >= self.mark.line;
+        loop {
+            if skip {
+                self.lookahead(1);
+                while !is_breakz(self.ch()) {
+                    self.skip();
+                    self.lookahead(1);
+                }
+                self.lookahead(2);
+                self.skip_line();
+            }
+            let mut col = 0;
+            loop {
+                self.lookahead(col + 1);
+                if self.buffer[col] != ' ' {
+                    break;
+                }
+                col += 1;
+            }
+            self.lookahead(col + 4);
+            if is_z(self.buffer[col]) || self.is_document_indicator(col) {
+                return;
+            }
+            skip = match self.buffer[col] {
+                '\r' | '\n' | '#' => true,
+                _ => col as isize > indent,
+            };
+            if !skip {
+                return;
+            }
         }
     }
     #[inline]
     pub fn get_error(&self) -> Option<ScanError> {
-        match self.error {
-            None => None,
-            Some(ref e) => Some(e.clone()),
-        }
+        self.error.clone()
     }
 
     #[inline]
@@ -267,7 +497,23 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return;
         }
         for _ in 0..(count - self.buffer.len()) {
-            self.buffer.push_back(self.rdr.next().unwrap_or('\0'));
+            let c = if self.input_exceeded {
+                None
+            } else {
+                self.rdr.next()
+            };
+            if c.is_some() {
+                self.chars_read += 1;
+                if let Some(max_input_chars) = self.max_input_chars {
+                    if self.chars_read > max_input_chars {
+                        // stop reading, the error is reported by `next_token`
+                        self.input_exceeded = true;
+                        self.buffer.push_back('\0');
+                        continue;
+                    }
+                }
+            }
+            self.buffer.push_back(c.unwrap_or('\0'));
         }
     }
     #[inline]
@@ -281,6 +527,22 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         } else {
             self.mark.col += 1;
         }
+        if self.recovery {
+            if c == '\n' {
+                self.line.clear();
+            } else {
+                self.line.push(c);
+            }
+        }
+    }
+    // a byte order mark does not take up a column
+    #[inline]
+    fn skip_bom(&mut self) {
+        let c = self.buffer.pop_front().unwrap();
+        self.mark.index += 1;
+        if self.recovery {
+            self.line.push(c);
+        }
     }
     #[inline]
     fn skip_line(&mut self) {
@@ -316,6 +578,40 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     pub fn mark(&self) -> Marker {
         self.mark
     }
+    // whether `---` or `...` starts at `buffer[pos]`, which needs a
+    // lookahead of `pos + 4`
+    fn is_document_indicator(&self, pos: usize) -> bool {
+        ((self.buffer[pos] == '-' && self.buffer[pos + 1] == '-' && self.buffer[pos + 2] == '-')
+            || (self.buffer[pos] == '.' && self.buffer[pos + 1] == '.' && self.buffer[pos + 2] == '.'))
+            && is_blankz(self.buffer[pos + 3])
+    }
+    // in recovery mode, whether the line after the line break at the current
+    // position ends a flow collection or quoted scalar, see `set_recovery`
+    fn at_recovery_boundary(&mut self) -> bool {
+        if !self.recovery {
+            return false;
+        }
+        self.lookahead(2);
+        let start = if self.buffer[0] == '\r' && self.buffer[1] == '\n' { 2 } else { 1 };
+        let mut pos = start;
+        loop {
+            self.lookahead(pos + 1);
+            if self.buffer[pos] != ' ' {
+                break;
+            }
+            pos += 1;
+        }
+        self.lookahead(pos + 4);
+        if pos == start && self.is_document_indicator(pos) {
+            return true;
+        }
+        match self.buffer[pos] {
+            '\0' | '\r' | '\n' | '#' => false,
+            // a closing bracket is often put at the indentation of the block
+            ']' | '}' if self.flow_level > 0 => false,
+            _ => ((pos - start) as isize) <= self.indent,
+        }
+    }
     #[inline]
     fn read_break(&mut self, s: &mut String) {
         if self.buffer[0] == '\r' && self.buffer[1] == '\n' {
@@ -352,7 +648,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.fetch_stream_start();
             return Ok(());
         }
-        self.skip_to_next_token();
+        self.skip_to_next_token()?;
+        self.bom_allowed = false;
 
         self.stale_simple_keys()?;
 
@@ -388,6 +685,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             && is_blankz(self.buffer[3])
         {
             self.fetch_document_indicator(TokenType::DocumentEnd)?;
+            self.bom_allowed = true;
             return Ok(());
         }
 
@@ -416,8 +714,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // plain scalar
             '-' if !is_blankz(nc) => self.fetch_plain_scalar(),
             ':' | '?' if !is_blankz(nc) && self.flow_level == 0 => self.fetch_plain_scalar(),
-            '%' | '@' | '`' => Err(ScanError::new(
+            '%' | '@' | '`' => Err(ScanError::new_with_kind(
                 self.mark,
+                ScanErrorKind::UnexpectedCharacter,
                 &format!("unexpected character: `{}'", c),
             )),
             _ => self.fetch_plain_scalar(),
@@ -430,7 +729,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if !self.token_available {
-            self.fetch_more_tokens()?;
+            let result = self.fetch_more_tokens();
+            if self.input_exceeded {
+                return Err(ScanError::new_with_kind(
+                    self.mark,
+                    ScanErrorKind::LimitExceeded(Limit::InputChars),
+                    &format!(
+                        "while reading the stream, exceeded the limit of {} characters",
+                        self.max_input_chars.unwrap()
+                    ),
+                ));
+            }
+            result?;
         }
         let t = self.tokens.pop_front().unwrap();
         self.token_available = false;
@@ -474,7 +784,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 && (sk.mark.line < self.mark.line || sk.mark.index + 1024 < self.mark.index)
             {
                 if sk.required {
-                    return Err(ScanError::new(self.mark, "simple key expect ':'"));
+                    return Err(ScanError::new_with_kind(self.mark, ScanErrorKind::UnexpectedToken, "simple key expect ':'"));
                 }
                 sk.possible = false;
             }
@@ -482,14 +792,36 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         Ok(())
     }
 
-    fn skip_to_next_token(&mut self) {
+    fn skip_to_next_token(&mut self) -> ScanResult {
         loop {
             self.lookahead(1);
-            // TODO(chenyh) BOM
             match self.ch() {
+                '\u{feff}' if self.mark.col == 0 => {
+                    // only at the start of the stream or of a document
+                    self.lookahead(5);
+                    let document_start = self.buffer[1] == '-'
+                        && self.buffer[2] == '-'
+                        && self.buffer[3] == '-'
+                        && is_blankz(self.buffer[4]);
+                    if !self.bom_allowed && !document_start {
+                        return Err(ScanError::new_with_kind(
+                            self.mark,
+                            ScanErrorKind::UnexpectedCharacter,
+                            "found a byte order mark inside a document",
+                        ));
+                    }
+                    self.skip_bom();
+                }
                 ' ' => self.skip(),
                 '\t' if self.flow_level > 0 || !self.simple_key_allowed => self.skip(),
                 '\n' | '\r' => {
+                    if self.flow_level > 0 && self.at_recovery_boundary() {
+                        return Err(ScanError::new_with_kind(
+                            self.mark,
+                            ScanErrorKind::UnexpectedEof,
+                            "while scanning a flow collection, found an unindented line before its end",
+                        ));
+                    }
                     self.lookahead(2);
                     self.skip_line();
                     if self.flow_level == 0 {
@@ -500,7 +832,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     self.skip();
                     self.lookahead(1);
                 },
-                _ => break,
+                _ => return Ok(()),
             }
         }
     }
@@ -511,7 +843,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.stream_start_produced = true;
         self.allow_simple_key();
         self.tokens
-            .push_back(Token::new(mark, TokenType::StreamStart(TEncoding::Utf8)));
+            .push_back(Token::new(mark, mark, TokenType::StreamStart(self.encoding)));
         self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
     }
 
@@ -520,6 +852,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if self.mark.col != 0 {
             self.mark.col = 0;
             self.mark.line += 1;
+            self.line.clear();
         }
 
         self.unroll_indent(-1);
@@ -527,7 +860,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.disallow_simple_key();
 
         self.tokens
-            .push_back(Token::new(self.mark, TokenType::StreamEnd));
+            .push_back(Token::new(self.mark, self.mark, TokenType::StreamEnd));
         Ok(())
     }
 
@@ -563,6 +896,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 // XXX return an empty TagDirective token
                 Token::new(
                     start_mark,
+                    self.mark,
                     TokenType::TagDirective(String::new(), String::new()),
                 )
                 // return Err(ScanError::new(start_mark,
@@ -584,8 +918,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if !is_breakz(self.ch()) {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 start_mark,
+                ScanErrorKind::InvalidDirective,
                 "while scanning a directive, did not find expected comment or line break",
             ));
         }
@@ -610,8 +945,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let major = self.scan_version_directive_number(mark)?;
 
         if self.ch() != '.' {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 *mark,
+                ScanErrorKind::InvalidDirective,
                 "while scanning a YAML directive, did not find expected digit or '.' character",
             ));
         }
@@ -620,7 +956,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         let minor = self.scan_version_directive_number(mark)?;
 
-        Ok(Token::new(*mark, TokenType::VersionDirective(major, minor)))
+        Ok(Token::new(*mark, self.mark, TokenType::VersionDirective(major, minor)))
     }
 
     fn scan_directive_name(&mut self) -> Result<String, ScanError> {
@@ -634,15 +970,17 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if string.is_empty() {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 start_mark,
+                ScanErrorKind::InvalidDirective,
                 "while scanning a directive, could not find expected directive name",
             ));
         }
 
         if !is_blankz(self.ch()) {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 start_mark,
+                ScanErrorKind::InvalidDirective,
                 "while scanning a directive, found unexpected non-alphabetical character",
             ));
         }
@@ -656,8 +994,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.lookahead(1);
         while is_digit(self.ch()) {
             if length + 1 > 9 {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     *mark,
+                    ScanErrorKind::InvalidDirective,
                     "while scanning a YAML directive, found extremely long version number",
                 ));
             }
@@ -668,8 +1007,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if length == 0 {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 *mark,
+                ScanErrorKind::InvalidDirective,
                 "while scanning a YAML directive, did not find expected version number",
             ));
         }
@@ -694,15 +1034,16 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         let is_secondary = handle == "!!";
-        let prefix = self.scan_tag_uri(true, is_secondary, &String::new(), mark)?;
+        let prefix = self.scan_tag_uri(true, is_secondary, "", mark)?;
 
         self.lookahead(1);
 
         if is_blankz(self.ch()) {
-            Ok(Token::new(*mark, TokenType::TagDirective(handle, prefix)))
+            Ok(Token::new(*mark, self.mark, TokenType::TagDirective(handle, prefix)))
         } else {
-            Err(ScanError::new(
+            Err(ScanError::new_with_kind(
                 *mark,
+                ScanErrorKind::InvalidDirective,
                 "while scanning TAG, did not find expected whitespace or line break",
             ))
         }
@@ -730,11 +1071,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // Eat '!<'
             self.skip();
             self.skip();
-            suffix = self.scan_tag_uri(false, false, &String::new(), &start_mark)?;
+            suffix = self.scan_tag_uri(false, false, "", &start_mark)?;
 
             if self.ch() != '>' {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     start_mark,
+                    ScanErrorKind::InvalidTag,
                     "while scanning a tag, did not find the expected '>'",
                 ));
             }
@@ -748,7 +1090,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 if handle == "!!" {
                     secondary = true;
                 }
-                suffix = self.scan_tag_uri(false, secondary, &String::new(), &start_mark)?;
+                suffix = self.scan_tag_uri(false, secondary, "", &start_mark)?;
             } else {
                 suffix = self.scan_tag_uri(false, false, &handle, &start_mark)?;
                 handle = "!".to_owned();
@@ -764,10 +1106,11 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.lookahead(1);
         if is_blankz(self.ch()) {
             // XXX: ex 7.2, an empty scalar can follow a secondary tag
-            Ok(Token::new(start_mark, TokenType::Tag(handle, suffix)))
+            Ok(Token::new(start_mark, self.mark, TokenType::Tag(handle, suffix)))
         } else {
-            Err(ScanError::new(
+            Err(ScanError::new_with_kind(
                 start_mark,
+                ScanErrorKind::InvalidTag,
                 "while scanning a tag, did not find expected whitespace or line break",
             ))
         }
@@ -777,8 +1120,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let mut string = String::new();
         self.lookahead(1);
         if self.ch() != '!' {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 *mark,
+                ScanErrorKind::InvalidTag,
                 "while scanning a tag, did not find expected '!'",
             ));
         }
@@ -801,8 +1145,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // It's either the '!' tag or not really a tag handle.  If it's a %TAG
             // directive, it's an error.  If it's a tag token, it must be a part of
             // URI.
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 *mark,
+                ScanErrorKind::InvalidDirective,
                 "while parsing a tag directive, did not find expected '!'",
             ));
         }
@@ -853,8 +1198,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if length == 0 {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 *mark,
+                ScanErrorKind::InvalidTag,
                 "while parsing a tag, did not find expected tag URI",
             ));
         }
@@ -869,8 +1215,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.lookahead(3);
 
             if !(self.ch() == '%' && is_hex(self.buffer[1]) && is_hex(self.buffer[2])) {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     *mark,
+                    ScanErrorKind::InvalidTag,
                     "while parsing a tag, did not find URI escaped octet",
                 ));
             }
@@ -883,8 +1230,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     _ if octet & 0xF0 == 0xE0 => 3,
                     _ if octet & 0xF8 == 0xF0 => 4,
                     _ => {
-                        return Err(ScanError::new(
+                        return Err(ScanError::new_with_kind(
                             *mark,
+                            ScanErrorKind::InvalidTag,
                             "while parsing a tag, found an incorrect leading UTF-8 octet",
                         ));
                     }
@@ -892,8 +1240,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 code = octet;
             } else {
                 if octet & 0xc0 != 0x80 {
-                    return Err(ScanError::new(
+                    return Err(ScanError::new_with_kind(
                         *mark,
+                        ScanErrorKind::InvalidTag,
                         "while parsing a tag, found an incorrect trailing UTF-8 octet",
                     ));
                 }
@@ -912,8 +1261,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         match char::from_u32(code) {
             Some(ch) => Ok(ch),
-            None => Err(ScanError::new(
+            None => Err(ScanError::new_with_kind(
                 *mark,
+                ScanErrorKind::InvalidTag,
                 "while parsing a tag, found an invalid UTF-8 codepoint",
             )),
         }
@@ -947,13 +1297,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             '?' | ':' | ',' | ']' | '}' | '%' | '@' | '`' => false,
             _ => true,
         } {
-            return Err(ScanError::new(start_mark, "while scanning an anchor or alias, did not find expected alphabetic or numeric character"));
+            return Err(ScanError::new_with_kind(start_mark, ScanErrorKind::InvalidAnchor, "while scanning an anchor or alias, did not find expected alphabetic or numeric character"));
         }
 
         if alias {
-            Ok(Token::new(start_mark, TokenType::Alias(string)))
+            Ok(Token::new(start_mark, self.mark, TokenType::Alias(string)))
         } else {
-            Ok(Token::new(start_mark, TokenType::Anchor(string)))
+            Ok(Token::new(start_mark, self.mark, TokenType::Anchor(string)))
         }
     }
 
@@ -968,7 +1318,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let start_mark = self.mark;
         self.skip();
 
-        self.tokens.push_back(Token::new(start_mark, tok));
+        self.tokens.push_back(Token::new(start_mark, self.mark, tok));
         Ok(())
     }
 
@@ -981,7 +1331,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let start_mark = self.mark;
         self.skip();
 
-        self.tokens.push_back(Token::new(start_mark, tok));
+        self.tokens.push_back(Token::new(start_mark, self.mark, tok));
         Ok(())
     }
 
@@ -993,7 +1343,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.skip();
 
         self.tokens
-            .push_back(Token::new(start_mark, TokenType::FlowEntry));
+            .push_back(Token::new(start_mark, self.mark, TokenType::FlowEntry));
         Ok(())
     }
 
@@ -1002,7 +1352,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.flow_level = self
             .flow_level
             .checked_add(1)
-            .ok_or_else(|| ScanError::new(self.mark, "recursion limit exceeded"))?;
+            .ok_or_else(|| ScanError::new_with_kind(self.mark, ScanErrorKind::LimitExceeded(Limit::Depth), "recursion limit exceeded"))?;
         Ok(())
     }
     fn decrease_flow_level(&mut self) {
@@ -1016,8 +1366,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if self.flow_level == 0 {
             // Check if we are allowed to start a new entry.
             if !self.simple_key_allowed {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     self.mark,
+                    ScanErrorKind::UnexpectedToken,
                     "block sequence entries are not allowed in this context",
                 ));
             }
@@ -1027,8 +1378,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.roll_indent(mark.col, None, TokenType::BlockSequenceStart, mark);
         } else {
             // - * only allowed in block
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 self.mark,
+                ScanErrorKind::UnexpectedToken,
                 r#""-" is only valid inside a block"#,
             ));
         }
@@ -1039,7 +1391,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.skip();
 
         self.tokens
-            .push_back(Token::new(start_mark, TokenType::BlockEntry));
+            .push_back(Token::new(start_mark, self.mark, TokenType::BlockEntry));
         Ok(())
     }
 
@@ -1054,7 +1406,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.skip();
         self.skip();
 
-        self.tokens.push_back(Token::new(mark, t));
+        self.tokens.push_back(Token::new(mark, self.mark, t));
         Ok(())
     }
 
@@ -1093,8 +1445,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.lookahead(1);
             if is_digit(self.ch()) {
                 if self.ch() == '0' {
-                    return Err(ScanError::new(
+                    return Err(ScanError::new_with_kind(
                         start_mark,
+                        ScanErrorKind::InvalidIndentation,
                         "while scanning a block scalar, found an intendation indicator equal to 0",
                     ));
                 }
@@ -1103,8 +1456,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
         } else if is_digit(self.ch()) {
             if self.ch() == '0' {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     start_mark,
+                    ScanErrorKind::InvalidIndentation,
                     "while scanning a block scalar, found an intendation indicator equal to 0",
                 ));
             }
@@ -1139,11 +1493,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         // Check if we are at the end of the line.
         if !is_breakz(self.ch()) {
-            return Err(ScanError::new(
+            return Err(ScanError::new_with_kind(
                 start_mark,
+                ScanErrorKind::UnexpectedCharacter,
                 "while scanning a block scalar, did not find expected comment or line break",
             ));
         }
+        let mut end_mark = self.mark;
 
         if is_break(self.ch()) {
             self.lookahead(2);
@@ -1162,7 +1518,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         self.lookahead(1);
 
-        let start_mark = self.mark;
+        let content_mark = self.mark;
 
         while self.mark.col == indent && !is_z(self.ch()) {
             // We are at the beginning of a non-empty line.
@@ -1179,14 +1535,17 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
             string.push_str(&trailing_breaks);
             trailing_breaks.clear();
+            self.check_scalar_length(&string, &content_mark)?;
 
             leading_blank = is_blank(self.ch());
 
             while !is_breakz(self.ch()) {
                 string.push(self.ch());
+                self.check_scalar_length(&string, &content_mark)?;
                 self.skip();
                 self.lookahead(1);
             }
+            end_mark = self.mark;
             // break on EOF
             if is_z(self.ch()) {
                 break;
@@ -1207,15 +1566,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if chomping == 1 {
             string.push_str(&trailing_breaks);
         }
+        self.check_scalar_length(&string, &content_mark)?;
 
         if literal {
             Ok(Token::new(
                 start_mark,
+                end_mark,
                 TokenType::Scalar(TScalarStyle::Literal, string),
             ))
         } else {
             Ok(Token::new(
                 start_mark,
+                end_mark,
                 TokenType::Scalar(TScalarStyle::Foled, string),
             ))
         }
@@ -1236,7 +1598,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
             // Check for a tab character messing the intendation.
             if (*indent == 0 || self.mark.col < *indent) && self.buffer[0] == '\t' {
-                return Err(ScanError::new(self.mark,
+                return Err(ScanError::new_with_kind(self.mark, ScanErrorKind::TabInIndentation,
                         "while scanning a block scalar, found a tab character where an intendation space is expected"));
             }
 
@@ -1294,15 +1656,17 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         && (self.buffer[2] == '.')))
                 && is_blankz(self.buffer[3])
             {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     start_mark,
+                    ScanErrorKind::UnexpectedEof,
                     "while scanning a quoted scalar, found unexpected document indicator",
                 ));
             }
 
             if is_z(self.ch()) {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     start_mark,
+                    ScanErrorKind::UnexpectedEof,
                     "while scanning a quoted scalar, found unexpected end of stream",
                 ));
             }
@@ -1360,8 +1724,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                             'u' => code_length = 4,
                             'U' => code_length = 8,
                             _ => {
-                                return Err(ScanError::new(
+                                return Err(ScanError::new_with_kind(
                                     start_mark,
+                                    ScanErrorKind::InvalidEscape,
                                     "while parsing a quoted scalar, found unknown escape character",
                                 ))
                             }
@@ -1374,7 +1739,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                             let mut value = 0u32;
                             for i in 0..code_length {
                                 if !is_hex(self.buffer[i]) {
-                                    return Err(ScanError::new(start_mark,
+                                    return Err(ScanError::new_with_kind(start_mark, ScanErrorKind::InvalidEscape,
                                         "while parsing a quoted scalar, did not find expected hexdecimal number"));
                                 }
                                 value = (value << 4) + as_hex(self.buffer[i]);
@@ -1383,7 +1748,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                             let ch = match char::from_u32(value) {
                                 Some(v) => v,
                                 None => {
-                                    return Err(ScanError::new(start_mark,
+                                    return Err(ScanError::new_with_kind(start_mark, ScanErrorKind::InvalidEscape,
                                         "while parsing a quoted scalar, found invalid Unicode character escape code"));
                                 }
                             };
@@ -1399,6 +1764,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         self.skip();
                     }
                 }
+                self.check_scalar_length(&string, &start_mark)?;
                 self.lookahead(2);
             }
             self.lookahead(1);
@@ -1419,6 +1785,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         self.skip();
                     }
                 } else {
+                    if self.at_recovery_boundary() {
+                        return Err(ScanError::new_with_kind(
+                            start_mark,
+                            ScanErrorKind::UnexpectedEof,
+                            "while scanning a quoted scalar, found an unindented line before the closing quote",
+                        ));
+                    }
                     self.lookahead(2);
                     // Check if it is a first line break.
                     if leading_blanks {
@@ -1455,15 +1828,18 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         // Eat the right quote.
         self.skip();
+        self.check_scalar_length(&string, &start_mark)?;
 
         if single {
             Ok(Token::new(
                 start_mark,
+                self.mark,
                 TokenType::Scalar(TScalarStyle::SingleQuoted, string),
             ))
         } else {
             Ok(Token::new(
                 start_mark,
+                self.mark,
                 TokenType::Scalar(TScalarStyle::DoubleQuoted, string),
             ))
         }
@@ -1482,12 +1858,14 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn scan_plain_scalar(&mut self) -> Result<Token, ScanError> {
         let indent = self.indent + 1;
         let start_mark = self.mark;
+        let mut end_mark = self.mark;
 
         let mut string = String::new();
         let mut leading_break = String::new();
         let mut trailing_breaks = String::new();
         let mut whitespaces = String::new();
         let mut leading_blanks = false;
+        let mut at_boundary = false;
 
         loop {
             /* Check for a document indicator. */
@@ -1502,14 +1880,19 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             {
                 break;
             }
+            // leave a byte order mark to `skip_to_next_token`
+            if self.mark.col == 0 && self.ch() == '\u{feff}' {
+                break;
+            }
 
             if self.ch() == '#' {
                 break;
             }
             while !is_blankz(self.ch()) {
                 if self.flow_level > 0 && self.ch() == ':' && is_blankz(self.ch()) {
-                    return Err(ScanError::new(
+                    return Err(ScanError::new_with_kind(
                         start_mark,
+                        ScanErrorKind::UnexpectedCharacter,
                         "while scanning a plain scalar, found unexpected ':'",
                     ));
                 }
@@ -1544,7 +1927,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 }
 
                 string.push(self.ch());
+                self.check_scalar_length(&string, &start_mark)?;
                 self.skip();
+                end_mark = self.mark;
                 self.lookahead(2);
             }
             // is the end?
@@ -1556,8 +1941,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             while is_blank(self.ch()) || is_break(self.ch()) {
                 if is_blank(self.ch()) {
                     if leading_blanks && (self.mark.col as isize) < indent && self.ch() == '\t' {
-                        return Err(ScanError::new(
+                        return Err(ScanError::new_with_kind(
                             start_mark,
+                            ScanErrorKind::TabInIndentation,
                             "while scanning a plain scalar, found a tab",
                         ));
                     }
@@ -1569,6 +1955,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                         self.skip();
                     }
                 } else {
+                    if self.flow_level > 0 && self.at_recovery_boundary() {
+                        at_boundary = true;
+                        break;
+                    }
                     self.lookahead(2);
                     // Check if it is a first line break
                     if leading_blanks {
@@ -1583,7 +1973,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
 
             // check intendation level
-            if self.flow_level == 0 && (self.mark.col as isize) < indent {
+            if at_boundary || (self.flow_level == 0 && (self.mark.col as isize) < indent) {
                 break;
             }
         }
@@ -1594,17 +1984,35 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         Ok(Token::new(
             start_mark,
+            end_mark,
             TokenType::Scalar(TScalarStyle::Plain, string),
         ))
     }
 
+    // called as the scalar grows, so that the limit also bounds the memory
+    // used while scanning it
+    fn check_scalar_length(&self, string: &str, start_mark: &Marker) -> ScanResult {
+        match self.max_scalar_length {
+            Some(max_scalar_length) if string.len() > max_scalar_length => Err(ScanError::new_with_kind(
+                *start_mark,
+                ScanErrorKind::LimitExceeded(Limit::ScalarLength),
+                &format!(
+                    "while scanning a scalar, exceeded the limit of {} bytes",
+                    max_scalar_length
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
     fn fetch_key(&mut self) -> ScanResult {
         let start_mark = self.mark;
         if self.flow_level == 0 {
             // Check if we are allowed to start a new key (not nessesary simple).
             if !self.simple_key_allowed {
-                return Err(ScanError::new(
+                return Err(ScanError::new_with_kind(
                     self.mark,
+                    ScanErrorKind::UnexpectedToken,
                     "mapping keys are not allowed in this context",
                 ));
             }
@@ -1625,7 +2033,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         self.skip();
-        self.tokens.push_back(Token::new(start_mark, TokenType::Key));
+        self.tokens.push_back(Token::new(start_mark, self.mark, TokenType::Key));
         Ok(())
     }
 
@@ -1634,16 +2042,17 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let start_mark = self.mark;
         if sk.possible {
             // insert simple key
-            let tok = Token::new(sk.mark, TokenType::Key);
+            let tok = Token::new(sk.mark, sk.mark, TokenType::Key);
             let tokens_parsed = self.tokens_parsed;
             self.insert_token(sk.token_number - tokens_parsed, tok);
 
-            // Add the BLOCK-MAPPING-START token if needed.
+            // Add the BLOCK-MAPPING-START token if needed. Like libyaml, the
+            // mapping starts at its first key, not at the ':'.
             self.roll_indent(
                 sk.mark.col,
                 Some(sk.token_number),
                 TokenType::BlockMappingStart,
-                start_mark,
+                sk.mark,
             );
 
             self.simple_keys.last_mut().unwrap().possible = false;
@@ -1652,8 +2061,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             // The ':' indicator follows a complex key.
             if self.flow_level == 0 {
                 if !self.simple_key_allowed {
-                    return Err(ScanError::new(
+                    return Err(ScanError::new_with_kind(
                         start_mark,
+                        ScanErrorKind::UnexpectedToken,
                         "mapping values are not allowed in this context",
                     ));
                 }
@@ -1673,7 +2083,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
         }
         self.skip();
-        self.tokens.push_back(Token::new(start_mark, TokenType::Value));
+        self.tokens.push_back(Token::new(start_mark, self.mark, TokenType::Value));
 
         Ok(())
     }
@@ -1688,8 +2098,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             self.indent = col as isize;
             let tokens_parsed = self.tokens_parsed;
             match number {
-                Some(n) => self.insert_token(n - tokens_parsed, Token::new(mark, tok)),
-                None => self.tokens.push_back(Token::new(mark, tok)),
+                Some(n) => self.insert_token(n - tokens_parsed, Token::new(mark, mark, tok)),
+                None => self.tokens.push_back(Token::new(mark, mark, tok)),
             }
         }
     }
@@ -1699,7 +2109,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return;
         }
         while self.indent > col {
-            self.tokens.push_back(Token::new(self.mark, TokenType::BlockEnd));
+            self.tokens.push_back(Token::new(self.mark, self.mark, TokenType::BlockEnd));
             self.indent = self.indents.pop().unwrap();
         }
     }
@@ -1723,7 +2133,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn remove_simple_key(&mut self) -> ScanResult {
         let last = self.simple_keys.last_mut().unwrap();
         if last.possible && last.required {
-            return Err(ScanError::new(self.mark, "simple key expected"));
+            return Err(ScanError::new_with_kind(self.mark, ScanErrorKind::UnexpectedToken, "simple key expected"));
         }
 
         last.possible = false;
@@ -1735,6 +2145,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 mod test {
     use super::TokenType::*;
     use super::*;
+    use settings::YamlStandardSettings;
 
     macro_rules! next {
         ($p:ident, $tk:pat) => {{
@@ -2098,6 +2509,110 @@ key:
         end!(p);
     }
 
+    #[test]
+    fn test_input_limits() {
+        let settings = YamlStandardSettings::new().max_input_chars(Some(8));
+        let mut p = Scanner::new_with_settings("a scalar".chars(), &settings);
+        next!(p, StreamStart(..));
+        next_scalar!(p, TScalarStyle::Plain, "a scalar");
+        next!(p, StreamEnd);
+        end!(p);
+
+        let settings = YamlStandardSettings::new().max_input_chars(Some(7));
+        let mut p = Scanner::new_with_settings("a scalar".chars(), &settings);
+        next!(p, StreamStart(..));
+        end!(p);
+        assert_eq!(p.get_error().unwrap().kind(), ScanErrorKind::LimitExceeded(Limit::InputChars));
+
+        let settings = YamlStandardSettings::new().max_scalar_length(Some(4));
+        let mut p = Scanner::new_with_settings("abcd".chars(), &settings);
+        next!(p, StreamStart(..));
+        next_scalar!(p, TScalarStyle::Plain, "abcd");
+        next!(p, StreamEnd);
+        end!(p);
+
+        let mut p = Scanner::new_with_settings("--- abcde".chars(), &settings);
+        next!(p, StreamStart(..));
+        next!(p, DocumentStart);
+        end!(p);
+        assert_eq!(p.get_error().unwrap().marker().col(), 4);
+        assert_eq!(p.get_error().unwrap().kind(), ScanErrorKind::LimitExceeded(Limit::ScalarLength));
+
+        // the limit stops the scanner inside a long line
+        for prefix in &["", "'", "\"", "|\n "] {
+            let read = ::std::cell::Cell::new(0);
+            let input = prefix.chars().chain(::std::iter::repeat('x').take(100_000));
+            let mut p = Scanner::new_with_settings(input.inspect(|_| read.set(read.get() + 1)), &settings);
+            next!(p, StreamStart(..));
+            end!(p);
+            assert_eq!(p.get_error().unwrap().kind(), ScanErrorKind::LimitExceeded(Limit::ScalarLength));
+            assert!(read.get() < 20, "{:?} read {}", prefix, read.get());
+        }
+    }
+
+    #[test]
+    fn test_block_mapping_start_mark() {
+        let s = "a:\n  bb: c\n? d\n: e\n";
+        let marks: Vec<_> = Scanner::new(s.chars())
+            .filter(|tok| tok.tokentype == BlockMappingStart)
+            .map(|tok| (tok.mark.line(), tok.mark.col()))
+            .collect();
+        // at the first simple key, not at its ':'
+        assert_eq!(marks, vec![(1, 0), (2, 2)]);
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let s = "- &x [a, 'b c']\n- |\n  d\n  e\n\n- f g  \n  h\n";
+        let spans: Vec<_> = Scanner::new(s.chars())
+            .map(|tok| ((tok.mark.line(), tok.mark.col()), (tok.end_mark.line(), tok.end_mark.col())))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ((1, 0), (1, 0)),   // StreamStart
+                ((1, 0), (1, 0)),   // BlockSequenceStart
+                ((1, 0), (1, 1)),   // BlockEntry
+                ((1, 2), (1, 4)),   // Anchor
+                ((1, 5), (1, 6)),   // FlowSequenceStart
+                ((1, 6), (1, 7)),   // a
+                ((1, 7), (1, 8)),   // FlowEntry
+                ((1, 9), (1, 14)),  // 'b c'
+                ((1, 14), (1, 15)), // FlowSequenceEnd
+                ((2, 0), (2, 1)),   // BlockEntry
+                ((2, 2), (4, 3)),   // |
+                ((6, 0), (6, 1)),   // BlockEntry
+                ((6, 2), (7, 3)),   // f g h
+                ((8, 0), (8, 0)),   // BlockEnd
+                ((8, 0), (8, 0)),   // StreamEnd
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bom() {
+        let s = "\u{feff}a: 1\n...\n\u{feff}--- b\n";
+        let mut p = Scanner::new(s.chars());
+        next!(p, StreamStart(TEncoding::Utf8));
+        next!(p, BlockMappingStart);
+        let tok = p.next().unwrap();
+        assert_eq!(tok.tokentype, Key);
+        assert_eq!((tok.mark.index(), tok.mark.col()), (1, 0));
+        next_scalar!(p, TScalarStyle::Plain, "a");
+        next!(p, Value);
+        next_scalar!(p, TScalarStyle::Plain, "1");
+        next!(p, BlockEnd);
+        next!(p, DocumentEnd);
+        next!(p, DocumentStart);
+        next_scalar!(p, TScalarStyle::Plain, "b");
+        next!(p, StreamEnd);
+        end!(p);
+
+        let mut p = Scanner::new("x".chars());
+        p.set_encoding(TEncoding::Utf16Le);
+        next!(p, StreamStart(TEncoding::Utf16Le));
+    }
+
     #[test]
     fn test_uri() {
         // TODO
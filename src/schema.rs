@@ -0,0 +1,442 @@
+/// The rules used to resolve the type of plain scalars without an explicit tag.
+///
+/// See the [YAML 1.2 recommended schemas](http://yaml.org/spec/1.2/spec.html#Schema)
+/// and the [YAML 1.1 type repository](http://yaml.org/type/).
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum Schema {
+    /// Every plain scalar is a string.
+    Failsafe,
+    /// Only `null`, `true`, `false` and JSON numbers are allowed as plain
+    /// scalars, anything else is an error.
+    Json,
+    /// The YAML 1.2 Core schema.
+    ///
+    /// Note that an empty plain scalar is kept as an empty string rather than
+    /// being resolved to null.
+    Core,
+    /// The YAML 1.1 types: `yes`/`no`/`on`/`off` booleans, `0b` binary and
+    /// `0` octal integers, `_` separators and base 60 numbers.
+    ///
+    /// As with `Core`, an empty plain scalar is kept as an empty string.
+    Yaml11,
+}
+
+/// The type a plain scalar resolves to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ResolvedScalar {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String,
+}
+
+impl Schema {
+    /// Resolve the type of the plain scalar `v`.
+    ///
+    /// Returns `None` if the schema does not allow the value, which only
+    /// happens with `Schema::Json`.
+    pub fn resolve(self, v: &str) -> Option<ResolvedScalar> {
+        match self {
+            Schema::Failsafe => Some(ResolvedScalar::String),
+            Schema::Json => resolve_json(v),
+            Schema::Core => Some(resolve_core(v)),
+            Schema::Yaml11 => Some(resolve_yaml11(v)),
+        }
+    }
+
+    /// The name of the schema, e.g. for error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Schema::Failsafe => "Failsafe",
+            Schema::Json => "JSON",
+            Schema::Core => "Core",
+            Schema::Yaml11 => "YAML 1.1",
+        }
+    }
+}
+
+fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.starts_with(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+fn is_digits(s: &str, radix: u32) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_digit(radix))
+}
+
+fn is_digits_with_underscores(s: &str, radix: u32) -> bool {
+    !s.is_empty() && s.chars().all(|c| c == '_' || c.is_digit(radix))
+}
+
+/// Split an optional leading `+` or `-` from `s`, returning whether it was `-`.
+fn split_sign(s: &str) -> (bool, &str) {
+    if let Some(rest) = strip_prefix(s, "-") {
+        (true, rest)
+    } else if let Some(rest) = strip_prefix(s, "+") {
+        (false, rest)
+    } else {
+        (false, s)
+    }
+}
+
+/// Parse unsigned digits in `radix`, ignoring `_` separators.
+fn parse_radix(neg: bool, digits: &str, radix: u32) -> Option<i64> {
+    let mut s: String = digits.chars().filter(|&c| c != '_').collect();
+    if neg {
+        s.insert(0, '-');
+    }
+    i64::from_str_radix(&s, radix).ok()
+}
+
+fn parse_inf_nan(v: &str) -> Option<f64> {
+    match v {
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Some(::std::f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => Some(::std::f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => Some(::std::f64::NAN),
+        _ => None,
+    }
+}
+
+/// `[eE][-+]?[0-9]+`, with a mandatory sign if `signed` is set.
+fn is_exponent(s: &str, signed: bool) -> bool {
+    let s = match strip_prefix(s, "e").or_else(|| strip_prefix(s, "E")) {
+        Some(s) => s,
+        None => return false,
+    };
+    if let Some(digits) = strip_prefix(s, "-").or_else(|| strip_prefix(s, "+")) {
+        is_digits(digits, 10)
+    } else {
+        !signed && is_digits(s, 10)
+    }
+}
+
+fn resolve_json(v: &str) -> Option<ResolvedScalar> {
+    match v {
+        "null" => return Some(ResolvedScalar::Null),
+        "true" => return Some(ResolvedScalar::Bool(true)),
+        "false" => return Some(ResolvedScalar::Bool(false)),
+        _ => {}
+    }
+    // -? ( 0 | [1-9] [0-9]* ) ( \. [0-9]* )? ( [eE] [-+]? [0-9]+ )?
+    let s = strip_prefix(v, "-").unwrap_or(v);
+    let int_len = s.find(|c: char| !c.is_digit(10)).unwrap_or(s.len());
+    if int_len == 0 || (int_len > 1 && s.starts_with('0')) {
+        return None;
+    }
+    let mut rest = &s[int_len..];
+    if rest.is_empty() {
+        if let Ok(i) = v.parse::<i64>() {
+            return Some(ResolvedScalar::Int(i));
+        }
+    }
+    if rest.starts_with('.') {
+        let frac_len = rest[1..].find(|c: char| !c.is_digit(10)).unwrap_or(rest.len() - 1);
+        rest = &rest[1 + frac_len..];
+    }
+    if rest.is_empty() || is_exponent(rest, false) {
+        v.parse::<f64>().ok().map(ResolvedScalar::Float)
+    } else {
+        None
+    }
+}
+
+fn resolve_core(v: &str) -> ResolvedScalar {
+    match v {
+        "null" | "Null" | "NULL" | "~" => return ResolvedScalar::Null,
+        "true" | "True" | "TRUE" => return ResolvedScalar::Bool(true),
+        "false" | "False" | "FALSE" => return ResolvedScalar::Bool(false),
+        _ => {}
+    }
+    if let Some(digits) = strip_prefix(v, "0o") {
+        if is_digits(digits, 8) {
+            if let Some(i) = parse_radix(false, digits, 8) {
+                return ResolvedScalar::Int(i);
+            }
+        }
+    }
+    if let Some(digits) = strip_prefix(v, "0x") {
+        if is_digits(digits, 16) {
+            if let Some(i) = parse_radix(false, digits, 16) {
+                return ResolvedScalar::Int(i);
+            }
+        }
+    }
+    let (neg, s) = split_sign(v);
+    if is_digits(s, 10) {
+        if let Some(i) = parse_radix(neg, s, 10) {
+            return ResolvedScalar::Int(i);
+        }
+    }
+    if let Some(f) = parse_inf_nan(v) {
+        return ResolvedScalar::Float(f);
+    }
+    // [-+]? ( \. [0-9]+ | [0-9]+ ( \. [0-9]* )? ) ( [eE] [-+]? [0-9]+ )?
+    let int_len = s.find(|c: char| !c.is_digit(10)).unwrap_or(s.len());
+    let mut rest = &s[int_len..];
+    if rest.starts_with('.') {
+        let frac_len = rest[1..].find(|c: char| !c.is_digit(10)).unwrap_or(rest.len() - 1);
+        if int_len == 0 && frac_len == 0 {
+            return ResolvedScalar::String;
+        }
+        rest = &rest[1 + frac_len..];
+    } else if int_len == 0 {
+        return ResolvedScalar::String;
+    }
+    if rest.is_empty() || is_exponent(rest, false) {
+        if let Ok(f) = v.parse::<f64>() {
+            return ResolvedScalar::Float(f);
+        }
+    }
+    ResolvedScalar::String
+}
+
+/// `[1-9][0-9_]*(:[0-5]?[0-9])+` (or `[0-9][0-9_]*...` for floats), as a
+/// value in base 60.
+fn parse_sexagesimal(s: &str, float: bool) -> Option<f64> {
+    let mut parts = s.split(':');
+    let first = parts.next().unwrap();
+    if !is_digits_with_underscores(first, 10)
+        || (!float && first.starts_with('0'))
+        || first.starts_with('_')
+    {
+        return None;
+    }
+    let mut value = match parse_radix(false, first, 10) {
+        Some(value) => value as f64,
+        None => return None,
+    };
+    let mut count = 0;
+    for part in parts {
+        // at most two digits, so the parsing can not fail
+        if part.is_empty() || part.len() > 2 || !is_digits(part, 10) || part.parse::<u8>().unwrap() > 59 {
+            return None;
+        }
+        value = value * 60.0 + part.parse::<f64>().unwrap();
+        count += 1;
+    }
+    if count == 0 {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn resolve_yaml11(v: &str) -> ResolvedScalar {
+    match v {
+        "null" | "Null" | "NULL" | "~" => return ResolvedScalar::Null,
+        "y" | "Y" | "yes" | "Yes" | "YES" | "true" | "True" | "TRUE" | "on" | "On" | "ON" => {
+            return ResolvedScalar::Bool(true)
+        }
+        "n" | "N" | "no" | "No" | "NO" | "false" | "False" | "FALSE" | "off" | "Off" | "OFF" => {
+            return ResolvedScalar::Bool(false)
+        }
+        _ => {}
+    }
+    if let Some(f) = parse_inf_nan(v) {
+        return ResolvedScalar::Float(f);
+    }
+    let (neg, s) = split_sign(v);
+    let int = if let Some(digits) = strip_prefix(s, "0b") {
+        if is_digits_with_underscores(digits, 2) {
+            parse_radix(neg, digits, 2)
+        } else {
+            None
+        }
+    } else if let Some(digits) = strip_prefix(s, "0x") {
+        if is_digits_with_underscores(digits, 16) {
+            parse_radix(neg, digits, 16)
+        } else {
+            None
+        }
+    } else if s.starts_with('0') {
+        if is_digits_with_underscores(s, 8) {
+            parse_radix(neg, s, 8)
+        } else {
+            None
+        }
+    } else if !s.starts_with('_') && is_digits_with_underscores(s, 10) {
+        parse_radix(neg, s, 10)
+    } else {
+        None
+    };
+    if let Some(i) = int {
+        return ResolvedScalar::Int(i);
+    }
+    if s.contains(':') {
+        // [-+]?[1-9][0-9_]*(:[0-5]?[0-9])+ or [-+]?[0-9][0-9_]*(:[0-5]?[0-9])+\.[0-9_]*
+        let (whole, frac) = match s.find('.') {
+            Some(pos) => (&s[..pos], Some(&s[pos + 1..])),
+            None => (s, None),
+        };
+        let sign = if neg { -1.0 } else { 1.0 };
+        match frac {
+            None => {
+                if let Some(value) = parse_sexagesimal(whole, false) {
+                    if value <= ::std::i64::MAX as f64 {
+                        return ResolvedScalar::Int(sign as i64 * value as i64);
+                    }
+                }
+            }
+            Some(frac) => {
+                if frac.is_empty() || is_digits_with_underscores(frac, 10) {
+                    if let Some(value) = parse_sexagesimal(whole, true) {
+                        let frac: String = frac.chars().filter(|&c| c != '_').collect();
+                        if let Ok(f) = format!("{}.{}", value, frac).parse::<f64>() {
+                            return ResolvedScalar::Float(sign * f);
+                        }
+                    }
+                }
+            }
+        }
+        return ResolvedScalar::String;
+    }
+    // [-+]?([0-9][0-9_]*)?\.[0-9._]*([eE][-+][0-9]+)?
+    let exp_pos = s.find(|c| c == 'e' || c == 'E').unwrap_or(s.len());
+    let (mantissa, exp) = s.split_at(exp_pos);
+    let dot = match mantissa.find('.') {
+        Some(dot) => dot,
+        None => return ResolvedScalar::String,
+    };
+    let (whole, frac) = (&mantissa[..dot], &mantissa[dot + 1..]);
+    if (whole.is_empty() || (!whole.starts_with('_') && is_digits_with_underscores(whole, 10)))
+        && frac.chars().all(|c| c == '.' || c == '_' || c.is_digit(10))
+        && (exp.is_empty() || is_exponent(exp, true))
+    {
+        let cleaned: String = v.chars().filter(|&c| c != '_').collect();
+        if let Ok(f) = cleaned.parse::<f64>() {
+            return ResolvedScalar::Float(f);
+        }
+    }
+    ResolvedScalar::String
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64;
+
+    fn check(schema: Schema, table: &[(&str, ResolvedScalar)]) {
+        for &(v, expected) in table {
+            assert_eq!(schema.resolve(v), Some(expected), "{:?} resolving {:?}", schema, v);
+        }
+    }
+
+    #[test]
+    fn test_failsafe() {
+        check(Schema::Failsafe, &[
+            ("null", ResolvedScalar::String),
+            ("true", ResolvedScalar::String),
+            ("1", ResolvedScalar::String),
+            ("1.5", ResolvedScalar::String),
+        ]);
+    }
+
+    #[test]
+    fn test_json() {
+        // http://yaml.org/spec/1.2/spec.html#id2804356
+        check(Schema::Json, &[
+            ("null", ResolvedScalar::Null),
+            ("true", ResolvedScalar::Bool(true)),
+            ("false", ResolvedScalar::Bool(false)),
+            ("0", ResolvedScalar::Int(0)),
+            ("-0", ResolvedScalar::Int(0)),
+            ("3", ResolvedScalar::Int(3)),
+            ("-19", ResolvedScalar::Int(-19)),
+            ("0.", ResolvedScalar::Float(0.0)),
+            ("-0.0", ResolvedScalar::Float(0.0)),
+            ("12e03", ResolvedScalar::Float(12000.0)),
+            ("-2E+05", ResolvedScalar::Float(-200000.0)),
+        ]);
+        for v in &["", "Null", "~", "True", "+1", "01", "0x1F", ".5", ".inf", "1e", "str"] {
+            assert_eq!(Schema::Json.resolve(v), None, "resolving {:?}", v);
+        }
+    }
+
+    #[test]
+    fn test_core() {
+        // http://yaml.org/spec/1.2/spec.html#id2805071
+        check(Schema::Core, &[
+            ("null", ResolvedScalar::Null),
+            ("Null", ResolvedScalar::Null),
+            ("NULL", ResolvedScalar::Null),
+            ("~", ResolvedScalar::Null),
+            ("", ResolvedScalar::String),
+            ("true", ResolvedScalar::Bool(true)),
+            ("True", ResolvedScalar::Bool(true)),
+            ("TRUE", ResolvedScalar::Bool(true)),
+            ("FALSE", ResolvedScalar::Bool(false)),
+            ("0", ResolvedScalar::Int(0)),
+            ("0o7", ResolvedScalar::Int(7)),
+            ("0x3A", ResolvedScalar::Int(58)),
+            ("-19", ResolvedScalar::Int(-19)),
+            ("+12", ResolvedScalar::Int(12)),
+            ("0.", ResolvedScalar::Float(0.0)),
+            ("-0.0", ResolvedScalar::Float(0.0)),
+            (".5", ResolvedScalar::Float(0.5)),
+            ("+12e03", ResolvedScalar::Float(12000.0)),
+            ("-2E+05", ResolvedScalar::Float(-200000.0)),
+            (".inf", ResolvedScalar::Float(f64::INFINITY)),
+            ("-.Inf", ResolvedScalar::Float(f64::NEG_INFINITY)),
+            ("+.INF", ResolvedScalar::Float(f64::INFINITY)),
+            ("yes", ResolvedScalar::String),
+            ("0b1", ResolvedScalar::String),
+            ("0x", ResolvedScalar::String),
+            ("0o8", ResolvedScalar::String),
+            ("0x-1", ResolvedScalar::String),
+            ("1_000", ResolvedScalar::String),
+            ("inf", ResolvedScalar::String),
+            ("nan", ResolvedScalar::String),
+            (".", ResolvedScalar::String),
+            ("1e", ResolvedScalar::String),
+            ("1:20", ResolvedScalar::String),
+        ]);
+        for v in &[".nan", ".NaN", ".NAN"] {
+            match Schema::Core.resolve(v) {
+                Some(ResolvedScalar::Float(f)) => assert!(f.is_nan()),
+                r => panic!("{:?} resolved to {:?}", v, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_yaml11() {
+        // http://yaml.org/type/bool.html, http://yaml.org/type/int.html,
+        // http://yaml.org/type/float.html, http://yaml.org/type/null.html
+        check(Schema::Yaml11, &[
+            ("~", ResolvedScalar::Null),
+            ("Null", ResolvedScalar::Null),
+            ("y", ResolvedScalar::Bool(true)),
+            ("Yes", ResolvedScalar::Bool(true)),
+            ("on", ResolvedScalar::Bool(true)),
+            ("N", ResolvedScalar::Bool(false)),
+            ("NO", ResolvedScalar::Bool(false)),
+            ("Off", ResolvedScalar::Bool(false)),
+            ("685230", ResolvedScalar::Int(685_230)),
+            ("+685_230", ResolvedScalar::Int(685_230)),
+            ("02472256", ResolvedScalar::Int(685_230)),
+            ("0x_0A_74_AE", ResolvedScalar::Int(685_230)),
+            ("0b1010_0111_0100_1010_1110", ResolvedScalar::Int(685_230)),
+            ("190:20:30", ResolvedScalar::Int(685_230)),
+            ("-190:20:30", ResolvedScalar::Int(-685_230)),
+            ("0", ResolvedScalar::Int(0)),
+            ("6.8523015e+5", ResolvedScalar::Float(685_230.15)),
+            ("685.230_15e+03", ResolvedScalar::Float(685_230.15)),
+            ("685_230.15", ResolvedScalar::Float(685_230.15)),
+            ("190:20:30.15", ResolvedScalar::Float(685_230.15)),
+            ("-.inf", ResolvedScalar::Float(f64::NEG_INFINITY)),
+            ("1e5", ResolvedScalar::String),
+            ("09", ResolvedScalar::String),
+            ("0o7", ResolvedScalar::String),
+            ("1:60", ResolvedScalar::String),
+            ("_1", ResolvedScalar::String),
+        ]);
+        match Schema::Yaml11.resolve(".NaN") {
+            Some(ResolvedScalar::Float(f)) => assert!(f.is_nan()),
+            r => panic!(".NaN resolved to {:?}", r),
+        }
+    }
+}
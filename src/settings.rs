@@ -1,5 +1,21 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use schema::Schema;
+use resolver::TagResolver;
+
+/// What to do when a mapping contains the same key more than once.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// Fail with an error referring to both occurrences of the key.
+    Error,
+    /// Keep the value of the first occurrence.
+    FirstWins,
+    /// Keep the value of the last occurrence.
+    LastWins,
+    /// Keep the value of the last occurrence and record a warning
+    /// referring to both occurrences of the key.
+    Collect,
+}
 
 pub trait YamlSettings: Clone {
     fn new() -> Self;
@@ -7,6 +23,16 @@ pub trait YamlSettings: Clone {
 
     fn is_aliases_allowed(&self) -> bool;
     fn is_multi_doc_allowed(&self) -> bool;
+    fn get_max_depth(&self) -> Option<usize>;
+    fn get_max_alias_expansion(&self) -> Option<usize>;
+    fn get_max_input_chars(&self) -> Option<usize>;
+    fn get_max_scalar_length(&self) -> Option<usize>;
+    fn get_max_nodes(&self) -> Option<usize>;
+    fn get_max_documents(&self) -> Option<usize>;
+    fn get_duplicate_key_policy(&self) -> DuplicateKeyPolicy;
+    fn get_schema(&self) -> Schema;
+    fn get_tag_resolvers(&self) -> Vec<Rc<TagResolver>>;
+    fn is_merge_keys_enabled(&self) -> bool;
 }
 
 #[derive(Clone)]
@@ -17,6 +43,16 @@ pub struct YamlStandardSettings {
 pub struct YamlStandardSettingsData {
     allow_aliases: bool,
     allow_multi_doc: bool,
+    max_depth: Option<usize>,
+    max_alias_expansion: Option<usize>,
+    max_input_chars: Option<usize>,
+    max_scalar_length: Option<usize>,
+    max_nodes: Option<usize>,
+    max_documents: Option<usize>,
+    duplicate_keys: DuplicateKeyPolicy,
+    schema: Schema,
+    tag_resolvers: Vec<Rc<TagResolver>>,
+    merge_keys: bool,
 }
 
 impl YamlStandardSettings {
@@ -25,6 +61,16 @@ impl YamlStandardSettings {
         let data = YamlStandardSettingsData {
             allow_aliases: true,
             allow_multi_doc: true,
+            max_depth: None,
+            max_alias_expansion: None,
+            max_input_chars: None,
+            max_scalar_length: None,
+            max_nodes: None,
+            max_documents: None,
+            duplicate_keys: DuplicateKeyPolicy::LastWins,
+            schema: Schema::Core,
+            tag_resolvers: Vec::new(),
+            merge_keys: false,
         };
         Self {
             v: Rc::new(RefCell::new(data)),
@@ -35,6 +81,16 @@ impl YamlStandardSettings {
         let data = YamlStandardSettingsData {
             allow_aliases: false,
             allow_multi_doc: false,
+            max_depth: Some(128),
+            max_alias_expansion: None,
+            max_input_chars: None,
+            max_scalar_length: None,
+            max_nodes: None,
+            max_documents: None,
+            duplicate_keys: DuplicateKeyPolicy::LastWins,
+            schema: Schema::Core,
+            tag_resolvers: Vec::new(),
+            merge_keys: false,
         };
         Self {
             v: Rc::new(RefCell::new(data)),
@@ -50,6 +106,69 @@ impl YamlStandardSettings {
         self.v.borrow_mut().allow_multi_doc = value;
         self
     }
+
+    /// Limit the nesting depth of block and flow collections, `None` means unlimited.
+    pub fn max_depth(self, value: Option<usize>) -> Self {
+        self.v.borrow_mut().max_depth = value;
+        self
+    }
+
+    /// Limit the total number of nodes produced by expanding aliases in a single document,
+    /// `None` means unlimited. Only meaningful when aliases are allowed.
+    pub fn max_alias_expansion(self, value: Option<usize>) -> Self {
+        self.v.borrow_mut().max_alias_expansion = value;
+        self
+    }
+
+    /// Limit the number of characters read from the input stream, `None` means unlimited.
+    pub fn max_input_chars(self, value: Option<usize>) -> Self {
+        self.v.borrow_mut().max_input_chars = value;
+        self
+    }
+
+    /// Limit the length of a single scalar in bytes, `None` means unlimited.
+    pub fn max_scalar_length(self, value: Option<usize>) -> Self {
+        self.v.borrow_mut().max_scalar_length = value;
+        self
+    }
+
+    /// Limit the number of nodes in a single document, including the nodes
+    /// produced by expanding aliases. `None` means unlimited.
+    pub fn max_nodes(self, value: Option<usize>) -> Self {
+        self.v.borrow_mut().max_nodes = value;
+        self
+    }
+
+    /// Limit the number of documents in a stream, `None` means unlimited.
+    pub fn max_documents(self, value: Option<usize>) -> Self {
+        self.v.borrow_mut().max_documents = value;
+        self
+    }
+
+    /// Choose how mappings with duplicate keys are handled.
+    pub fn duplicate_keys(self, value: DuplicateKeyPolicy) -> Self {
+        self.v.borrow_mut().duplicate_keys = value;
+        self
+    }
+
+    /// Choose the schema used to resolve the type of plain scalars.
+    pub fn schema(self, value: Schema) -> Self {
+        self.v.borrow_mut().schema = value;
+        self
+    }
+
+    /// Register a resolver for scalars with custom tags. Resolvers are tried
+    /// in the order they were registered.
+    pub fn tag_resolver<R: TagResolver + 'static>(self, resolver: R) -> Self {
+        self.v.borrow_mut().tag_resolvers.push(Rc::new(resolver));
+        self
+    }
+
+    /// Enable YAML 1.1 merge keys, e.g. `<<: *defaults`.
+    pub fn merge_keys(self, value: bool) -> Self {
+        self.v.borrow_mut().merge_keys = value;
+        self
+    }
 }
 
 impl YamlSettings for YamlStandardSettings {
@@ -69,4 +188,44 @@ impl YamlSettings for YamlStandardSettings {
     fn is_multi_doc_allowed(&self) -> bool {
         self.v.borrow().allow_multi_doc
     }
+
+    fn get_max_depth(&self) -> Option<usize> {
+        self.v.borrow().max_depth
+    }
+
+    fn get_max_alias_expansion(&self) -> Option<usize> {
+        self.v.borrow().max_alias_expansion
+    }
+
+    fn get_max_input_chars(&self) -> Option<usize> {
+        self.v.borrow().max_input_chars
+    }
+
+    fn get_max_scalar_length(&self) -> Option<usize> {
+        self.v.borrow().max_scalar_length
+    }
+
+    fn get_max_nodes(&self) -> Option<usize> {
+        self.v.borrow().max_nodes
+    }
+
+    fn get_max_documents(&self) -> Option<usize> {
+        self.v.borrow().max_documents
+    }
+
+    fn get_duplicate_key_policy(&self) -> DuplicateKeyPolicy {
+        self.v.borrow().duplicate_keys
+    }
+
+    fn get_schema(&self) -> Schema {
+        self.v.borrow().schema
+    }
+
+    fn get_tag_resolvers(&self) -> Vec<Rc<TagResolver>> {
+        self.v.borrow().tag_resolvers.clone()
+    }
+
+    fn is_merge_keys_enabled(&self) -> bool {
+        self.v.borrow().merge_keys
+    }
 }
@@ -1,8 +1,8 @@
 use linked_hash_map::LinkedHashMap;
-use parser::AnchorId;
+use parser::{AnchorId, Tag};
 use loader::parse_f64;
+use schema::{ResolvedScalar, Schema};
 use std::f64;
-use std::i64;
 use std::ops::Index;
 use std::string;
 use std::vec;
@@ -47,6 +47,9 @@ pub enum Yaml {
     Hash(self::Hash),
     /// Alias, not fully supported yet.
     Alias(AnchorId),
+    /// A node with a tag the loader does not recognize, e.g. `!Ref foo`.
+    /// Scalars with such a tag are kept as `Yaml::String`.
+    Tagged(Tag, Box<Yaml>),
     /// YAML null, e.g. `null` or `~`.
     Null,
     /// Accessing a nonexistent node via the Index trait returns `BadValue`. This
@@ -146,29 +149,21 @@ impl Yaml {
     // Not implementing FromStr because there is no possibility of Error.
     // This function falls back to Yaml::String if nothing else matches.
     pub fn from_str(v: &str) -> Yaml {
-        if v.starts_with("0x") {
-            let n = i64::from_str_radix(&v[2..], 16);
-            if n.is_ok() {
-                return Yaml::Integer(n.unwrap());
-            }
-        }
-        if v.starts_with("0o") {
-            let n = i64::from_str_radix(&v[2..], 8);
-            if n.is_ok() {
-                return Yaml::Integer(n.unwrap());
-            }
-        }
-        if v.starts_with('+') && v[1..].parse::<i64>().is_ok() {
-            return Yaml::Integer(v[1..].parse::<i64>().unwrap());
-        }
-        match v {
-            "~" | "null" => Yaml::Null,
-            "true" => Yaml::Boolean(true),
-            "false" => Yaml::Boolean(false),
-            _ if v.parse::<i64>().is_ok() => Yaml::Integer(v.parse::<i64>().unwrap()),
-            // try parsing as f64
-            _ if parse_f64(v).is_some() => Yaml::Real(v.to_owned()),
-            _ => Yaml::String(v.to_owned()),
+        Yaml::from_str_with_schema(v, Schema::Core)
+    }
+
+    /// Convert the string to the appropriate YAML type using the rules of
+    /// `schema`. Values the schema does not allow become `Yaml::BadValue`.
+    pub fn from_str_with_schema(v: &str, schema: Schema) -> Yaml {
+        match schema.resolve(v) {
+            Some(ResolvedScalar::Null) => Yaml::Null,
+            Some(ResolvedScalar::Bool(b)) => Yaml::Boolean(b),
+            Some(ResolvedScalar::Int(i)) => Yaml::Integer(i),
+            // keep the original representation when as_f64 can read it back
+            Some(ResolvedScalar::Float(_)) if parse_f64(v).is_some() => Yaml::Real(v.to_owned()),
+            Some(ResolvedScalar::Float(f)) => Yaml::Real(f.to_string()),
+            Some(ResolvedScalar::String) => Yaml::String(v.to_owned()),
+            None => Yaml::BadValue,
         }
     }
 }
@@ -228,7 +223,20 @@ impl Iterator for YamlIter {
 mod test {
     use std::f64;
     use yaml::*;
-    use loader::{yaml_load_from_str, yaml_load_doc_from_str, yaml_load_doc_from_str_safe};
+    use loader::{yaml_load_from_str, yaml_load_doc_from_str, yaml_load_doc_from_str_safe,
+                 yaml_load_from_str_safe, yaml_load_from_str_with_settings,
+                 yaml_load_doc_from_str_with_settings, yaml_load_from_str_with_warnings,
+                 yaml_load_from_reader, yaml_load_from_slice, yaml_load_marked_from_str_with_settings,
+                 YamlDocumentIter, YamlLoader};
+    use builder::{YamlBuilder, YamlStandardBuilder};
+    use marked_yaml::MarkedNode;
+    use reader::LoadError;
+    use settings::{YamlStandardSettings, DuplicateKeyPolicy};
+    use schema::Schema;
+    use resolver::{TagNode, TagNodeBuilder, TagResolver};
+    use scanner::{Limit, Marker, ScanError, ScanErrorKind};
+    use parser::{Event, MarkedEventReceiver, Tag};
+    use scanner::TScalarStyle;
     #[test]
     fn test_coerce() {
         let s = "---
@@ -286,6 +294,36 @@ a7: 你好
         assert_eq!(out.len(), 3);
     }
 
+    #[test]
+    fn test_multi_doc_disallowed() {
+        let s = "
+'a scalar'
+---
+'a scalar'
+";
+        let err = yaml_load_from_str_safe(s).unwrap_err();
+        assert_eq!(err.marker().line(), 3);
+        assert_eq!(err.marker().col(), 0);
+
+        let settings = YamlStandardSettings::new().allow_multi_doc(false);
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 3);
+
+        let settings = YamlStandardSettings::new_safe().allow_multi_doc(true);
+        let out = yaml_load_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(out.len(), 2);
+    }
+
+    #[test]
+    fn test_single_doc_disallowed_multi() {
+        let out = yaml_load_from_str_safe("---\na: 1\n...\n").unwrap();
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0]["a"].as_i64().unwrap(), 1);
+
+        let out = yaml_load_from_str_safe("a: 1").unwrap();
+        assert_eq!(out.len(), 1);
+    }
+
     #[test]
     fn test_anchor() {
         let s = "
@@ -586,6 +624,148 @@ subcommands3:
         assert!(yaml_load_from_str(&s).is_err());
     }
 
+    #[test]
+    fn test_max_depth_block() {
+        let settings = YamlStandardSettings::new().max_depth(Some(3));
+
+        let s = "a:\n  b:\n    - c\n";
+        let doc = yaml_load_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc[0]["a"]["b"][0].as_str().unwrap(), "c");
+
+        let s = "a:\n  b:\n    c:\n      d: e\n";
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 4);
+        assert_eq!(err.marker().col(), 6);
+
+        let s = "- - - - a\n";
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 1);
+        assert_eq!(err.marker().col(), 6);
+    }
+
+    #[test]
+    fn test_max_depth_flow() {
+        let settings = YamlStandardSettings::new().max_depth(Some(3));
+
+        let s = "a: [{b: c}]";
+        let doc = yaml_load_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc[0]["a"][0]["b"].as_str().unwrap(), "c");
+
+        let s = "a: [{b: [c]}]";
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 1);
+        assert_eq!(err.marker().col(), 8);
+    }
+
+    #[test]
+    fn test_max_depth_safe() {
+        assert!(yaml_load_from_str_safe("a:\n  b: c\n").is_ok());
+
+        let s = "- ".repeat(1_000) + "a";
+        assert!(yaml_load_from_str_safe(&s).is_err());
+    }
+
+    #[test]
+    fn test_max_input_chars() {
+        let s = "a: 1\nb: 2\n";
+        let settings = YamlStandardSettings::new().max_input_chars(Some(s.len()));
+        assert!(yaml_load_from_str_with_settings(s, &settings).is_ok());
+
+        let settings = YamlStandardSettings::new().max_input_chars(Some(s.len() - 1));
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert!(err.to_string().contains("characters"));
+    }
+
+    #[test]
+    fn test_max_scalar_length() {
+        let settings = YamlStandardSettings::new().max_scalar_length(Some(5));
+        let doc = yaml_load_doc_from_str_with_settings("[abcde, 'abcde', \"abcde\"]", &settings).unwrap();
+        assert_eq!(doc[2].as_str().unwrap(), "abcde");
+
+        for s in &["a: abcdef", "a: 'abc def'", "a: \"abc\\ndef\"", "a: |\n  abc\n  def\n", "a: >\n  abc\n\n  de\n"] {
+            let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+            assert!(err.to_string().contains("bytes"), "{}", s);
+            assert_eq!(err.marker().line(), if s.contains('\n') { 2 } else { 1 }, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_max_nodes() {
+        let settings = YamlStandardSettings::new().max_nodes(Some(7));
+        assert!(yaml_load_from_str_with_settings("a: [1, 2]\nb: c\n", &settings).is_ok());
+        let err = yaml_load_from_str_with_settings("a: [1, 2]\nb: [c]\n", &settings).unwrap_err();
+        assert!(err.to_string().contains("nodes"));
+
+        // aliases count with the size of the anchored node
+        let s = "a: &x [1, 2]\nb: *x\n";
+        let settings = YamlStandardSettings::new().max_nodes(Some(9));
+        assert!(yaml_load_from_str_with_settings(s, &settings).is_ok());
+        let settings = YamlStandardSettings::new().max_nodes(Some(8));
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 2);
+        assert_eq!(err.marker().col(), 3);
+
+        // the limit applies to each document separately
+        let s = "[1, 2]\n---\n[1, 2]\n";
+        let settings = YamlStandardSettings::new().max_nodes(Some(3));
+        assert_eq!(yaml_load_from_str_with_settings(s, &settings).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_max_documents() {
+        let s = "a\n---\nb\n---\nc\n";
+        let settings = YamlStandardSettings::new().max_documents(Some(3));
+        assert_eq!(yaml_load_from_str_with_settings(s, &settings).unwrap().len(), 3);
+
+        let settings = YamlStandardSettings::new().max_documents(Some(2));
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 4);
+    }
+
+    #[test]
+    fn test_limit_kinds() {
+        let limit = |s: &str, settings: YamlStandardSettings| {
+            match yaml_load_from_str_with_settings(s, &settings).unwrap_err().kind() {
+                ScanErrorKind::LimitExceeded(limit) => limit,
+                kind => panic!("unexpected {:?}", kind),
+            }
+        };
+        assert_eq!(limit("[[1]]", YamlStandardSettings::new().max_depth(Some(1))), Limit::Depth);
+        assert_eq!(limit("a: bc", YamlStandardSettings::new().max_input_chars(Some(4))), Limit::InputChars);
+        assert_eq!(limit("a: bc", YamlStandardSettings::new().max_scalar_length(Some(1))), Limit::ScalarLength);
+        assert_eq!(limit("[1, 2]", YamlStandardSettings::new().max_nodes(Some(2))), Limit::Nodes);
+        assert_eq!(limit("a\n---\nb", YamlStandardSettings::new().max_documents(Some(1))), Limit::Documents);
+        assert_eq!(limit("- &a [1]\n- *a", YamlStandardSettings::new().max_alias_expansion(Some(1))),
+                   Limit::AliasExpansion);
+        let mut deep = String::new();
+        for _ in 0..300 {
+            deep.push('[');
+        }
+        assert_eq!(limit(&deep, YamlStandardSettings::new()), Limit::Depth);
+    }
+
+    #[test]
+    fn test_limit_on_event() {
+        let settings = YamlStandardSettings::new().max_documents(Some(1));
+        let builder = YamlStandardBuilder::new(&settings);
+        let mut loader = YamlLoader::new(&settings, &builder);
+        let mark = Marker::new(0, 1, 0);
+        loader.on_event(Event::StreamStart, mark);
+        for _ in 0..2 {
+            loader.on_event(Event::DocumentStart(None), mark);
+            let value = "a".to_owned();
+            loader.on_event(Event::Scalar{value, style: TScalarStyle::Plain, anchor: None, tag: None}, mark);
+            loader.on_event(Event::DocumentEnd, mark);
+        }
+        loader.on_event(Event::StreamEnd, mark);
+        match loader.take_error().unwrap().kind() {
+            ScanErrorKind::LimitExceeded(limit) => assert_eq!(limit, Limit::Documents),
+            kind => panic!("unexpected {:?}", kind),
+        }
+        assert!(loader.take_error().is_none());
+        assert_eq!(builder.into_documents(), vec![Yaml::String("a".to_owned())]);
+    }
+
     #[test]
     fn test_lol_bomb() {
         let s = "
@@ -601,5 +781,513 @@ i: &i [*h,*h,*h,*h,*h,*h,*h,*h,*h]
 ";
         let doc = yaml_load_doc_from_str_safe(s).unwrap();
         assert!(doc["i"][0].is_badvalue());
+    }
+
+    #[test]
+    fn test_lol_bomb_expansion_limit() {
+        let s = "
+a: &a [\"lol\",\"lol\",\"lol\",\"lol\",\"lol\",\"lol\",\"lol\",\"lol\",\"lol\"]
+b: &b [*a,*a,*a,*a,*a,*a,*a,*a,*a]
+c: &c [*b,*b,*b,*b,*b,*b,*b,*b,*b]
+d: &d [*c,*c,*c,*c,*c,*c,*c,*c,*c]
+e: &e [*d,*d,*d,*d,*d,*d,*d,*d,*d]
+f: &f [*e,*e,*e,*e,*e,*e,*e,*e,*e]
+g: &g [*f,*f,*f,*f,*f,*f,*f,*f,*f]
+h: &h [*g,*g,*g,*g,*g,*g,*g,*g,*g]
+i: &i [*h,*h,*h,*h,*h,*h,*h,*h,*h]
+";
+        let settings = YamlStandardSettings::new_safe()
+            .allow_aliases(true)
+            .max_alias_expansion(Some(10_000));
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 6);
+    }
+
+    #[test]
+    fn test_alias_expansion_limit() {
+        let s = "
+a: &a [1, 2, 3]
+b: *a
+c: *a
+";
+        // every expansion of `a` produces 4 nodes
+        let settings = YamlStandardSettings::new().max_alias_expansion(Some(8));
+        let doc = yaml_load_doc_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc["c"][2].as_i64().unwrap(), 3);
+
+        let settings = YamlStandardSettings::new().max_alias_expansion(Some(7));
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 4);
+        assert_eq!(err.marker().col(), 3);
+    }
+
+    #[test]
+    fn test_alias_expansion_limit_per_document() {
+        let s = "
+a: &a [1, 2, 3]
+b: *a
+---
+a: &a [1, 2, 3]
+b: *a
+";
+        let settings = YamlStandardSettings::new().max_alias_expansion(Some(4));
+        let out = yaml_load_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(out.len(), 2);
     }    
+
+    #[test]
+    fn test_duplicate_keys() {
+        let s = "
+a: 1
+b: 2
+a: 3
+";
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        assert_eq!(doc["a"].as_i64().unwrap(), 3);
+        assert_eq!(doc.as_hash().unwrap().len(), 2);
+
+        let settings = YamlStandardSettings::new().duplicate_keys(DuplicateKeyPolicy::LastWins);
+        let doc = yaml_load_doc_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc["a"].as_i64().unwrap(), 3);
+
+        let settings = YamlStandardSettings::new().duplicate_keys(DuplicateKeyPolicy::FirstWins);
+        let doc = yaml_load_doc_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc["a"].as_i64().unwrap(), 1);
+        assert_eq!(doc["b"].as_i64().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_duplicate_keys_error() {
+        let s = "
+x:
+  a: 1
+  b: 2
+  a: 3
+";
+        let settings = YamlStandardSettings::new().duplicate_keys(DuplicateKeyPolicy::Error);
+        let err = yaml_load_from_str_with_settings(s, &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 5);
+        assert_eq!(err.marker().col(), 2);
+        let first = err.context_marker().unwrap();
+        assert_eq!(first.line(), 3);
+        assert_eq!(first.col(), 2);
+
+        // the same key in different mappings is fine
+        let s = "
+- {a: 1, b: 2}
+- {a: 3, b: 4}
+";
+        assert!(yaml_load_from_str_with_settings(s, &settings).is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_keys_collect() {
+        let s = "
+a: 1
+{x: y}: 2
+a: 3
+{x: y}: 4
+";
+        let settings = YamlStandardSettings::new().duplicate_keys(DuplicateKeyPolicy::Collect);
+        let (out, warnings) = yaml_load_from_str_with_warnings(s, &settings).unwrap();
+        assert_eq!(out[0]["a"].as_i64().unwrap(), 3);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].marker().line(), 4);
+        assert_eq!(warnings[0].context_marker().unwrap().line(), 2);
+        assert_eq!(warnings[1].marker().line(), 5);
+        assert_eq!(warnings[1].context_marker().unwrap().line(), 3);
+    }
+
+    #[test]
+    fn test_schema() {
+        let s = "
+- yes
+- True
+- 0o17
+- 017
+- 1_000
+- 1:30
+- .NaN
+- '1'
+- !!int 0b11
+- !!bool on
+";
+
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        assert_eq!(doc[0].as_str().unwrap(), "yes");
+        assert!(doc[1].as_bool().unwrap());
+        assert_eq!(doc[2].as_i64().unwrap(), 15);
+        assert_eq!(doc[3].as_i64().unwrap(), 17);
+        assert_eq!(doc[4].as_str().unwrap(), "1_000");
+        assert_eq!(doc[5].as_str().unwrap(), "1:30");
+        assert!(doc[6].as_f64().unwrap().is_nan());
+        assert_eq!(doc[7].as_str().unwrap(), "1");
+        assert!(doc[8].is_badvalue());
+        assert!(doc[9].is_badvalue());
+
+        let settings = YamlStandardSettings::new().schema(Schema::Yaml11);
+        let doc = yaml_load_doc_from_str_with_settings(s, &settings).unwrap();
+        assert!(doc[0].as_bool().unwrap());
+        assert!(doc[1].as_bool().unwrap());
+        assert_eq!(doc[2].as_str().unwrap(), "0o17");
+        assert_eq!(doc[3].as_i64().unwrap(), 15);
+        assert_eq!(doc[4].as_i64().unwrap(), 1000);
+        assert_eq!(doc[5].as_i64().unwrap(), 90);
+        assert_eq!(doc[8].as_i64().unwrap(), 3);
+        assert!(doc[9].as_bool().unwrap());
+
+        let settings = YamlStandardSettings::new().schema(Schema::Failsafe);
+        let doc = yaml_load_doc_from_str_with_settings("[null, true, 1, !!int 1]", &settings).unwrap();
+        assert_eq!(doc[0].as_str().unwrap(), "null");
+        assert_eq!(doc[1].as_str().unwrap(), "true");
+        assert_eq!(doc[2].as_str().unwrap(), "1");
+        assert_eq!(doc[3].as_i64().unwrap(), 1);
+
+        let settings = YamlStandardSettings::new().schema(Schema::Json);
+        let doc = yaml_load_doc_from_str_with_settings("[null, true, -1, 1.5e3, 'x']", &settings).unwrap();
+        assert!(doc[0].is_null());
+        assert!(doc[1].as_bool().unwrap());
+        assert_eq!(doc[2].as_i64().unwrap(), -1);
+        assert_eq!(doc[3].as_f64().unwrap(), 1500.0);
+        assert_eq!(doc[4].as_str().unwrap(), "x");
+        let err = yaml_load_from_str_with_settings("\"a\": [1, x]", &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 1);
+        assert_eq!(err.marker().col(), 9);
+        assert_eq!(err.info(), "while resolving a plain scalar, found a value that is not allowed by the JSON schema");
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_str_to_node() {
+        let settings = YamlStandardSettings::new().schema(Schema::Json);
+        let builder = YamlStandardBuilder::new(&settings);
+        let mut loader = YamlLoader::new(&settings, &builder);
+        let mark = Marker::new(0, 1, 0);
+        // the old function resolves with the Core schema, whatever the settings
+        let nodes = vec![
+            loader.str_to_node("yes", mark),
+            loader.str_to_node("0x10", mark),
+            loader.resolve_plain_scalar("16", mark).unwrap(),
+        ];
+        let err = loader.resolve_plain_scalar("yes", mark).unwrap_err();
+        assert_eq!(err.kind(), ScanErrorKind::InvalidValue);
+
+        let mut b = builder.clone();
+        let doc = b.new_document(mark);
+        let seq = b.new_sequence(mark);
+        for node in nodes {
+            b.add_to_sequence(seq, node);
+        }
+        b.close_sequence(seq);
+        b.close_document(doc, seq);
+        assert_eq!(builder.into_documents(),
+                   vec![Yaml::Array(vec![Yaml::String("yes".to_owned()), Yaml::Integer(16), Yaml::Integer(16)])]);
+    }
+
+    #[test]
+    fn test_from_str_with_schema() {
+        assert_eq!(Yaml::from_str("True"), Yaml::Boolean(true));
+        assert_eq!(Yaml::from_str("yes"), Yaml::String("yes".to_owned()));
+        assert_eq!(Yaml::from_str("1e3"), Yaml::Real("1e3".to_owned()));
+        assert_eq!(Yaml::from_str_with_schema("yes", Schema::Yaml11), Yaml::Boolean(true));
+        assert_eq!(Yaml::from_str_with_schema("1_000.5", Schema::Yaml11).as_f64(), Some(1000.5));
+        assert_eq!(Yaml::from_str_with_schema("1", Schema::Failsafe), Yaml::String("1".to_owned()));
+        assert_eq!(Yaml::from_str_with_schema("x", Schema::Json), Yaml::BadValue);
+    }
+
+    struct TestResolver;
+
+    impl TagResolver for TestResolver {
+        fn resolve(
+            &self,
+            tag: &Tag,
+            value: &str,
+            marker: Marker,
+            builder: &mut TagNodeBuilder,
+        ) -> Option<Result<TagNode, ScanError>> {
+            match (tag.handle.as_str(), tag.suffix.as_str()) {
+                ("!", "env") => Some(match value {
+                    "HOME" => Ok(builder.new_string("/home/test".to_owned())),
+                    _ => Err(ScanError::new(marker, "unknown variable")),
+                }),
+                ("!", "seconds") => Some(Ok(if value.ends_with('m') {
+                    builder.new_int(value[..value.len() - 1].parse::<i64>().unwrap() * 60)
+                } else {
+                    builder.new_badvalue()
+                })),
+                ("!", "pair") => {
+                    let (key, item) = value.split_at(value.find('=').unwrap());
+                    let key = builder.new_string(key.to_owned());
+                    let item = builder.new_string(item[1..].to_owned());
+                    let pair = builder.new_mapping(vec![(key, item)]);
+                    Some(pair.map(|pair| builder.new_tagged(tag.clone(), pair)))
+                }
+                _ if tag.is_core("int") && value == "many" => Some(Ok(builder.new_int(1000))),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_tag_resolver() {
+        let s = "
+home: !env HOME
+quoted: !env 'HOME'
+timeout: !seconds 2m
+bad: !seconds 2
+int: !!int many
+default: !!int 12
+other: !other value
+pair: !pair a=b
+";
+        let settings = YamlStandardSettings::new().tag_resolver(TestResolver);
+        let doc = yaml_load_doc_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc["home"].as_str().unwrap(), "/home/test");
+        assert_eq!(doc["quoted"].as_str().unwrap(), "/home/test");
+        assert_eq!(doc["timeout"].as_i64().unwrap(), 120);
+        assert!(doc["bad"].is_badvalue());
+        assert_eq!(doc["int"].as_i64().unwrap(), 1000);
+        assert_eq!(doc["default"].as_i64().unwrap(), 12);
+        assert_eq!(
+            doc["other"],
+            Yaml::Tagged(
+                Tag { handle: "!".to_owned(), suffix: "other".to_owned() },
+                Box::new(Yaml::String("value".to_owned()))
+            )
+        );
+        let mut pair = Hash::new();
+        pair.insert(Yaml::from_str("a"), Yaml::from_str("b"));
+        assert_eq!(
+            doc["pair"],
+            Yaml::Tagged(Tag { handle: "!".to_owned(), suffix: "pair".to_owned() }, Box::new(Yaml::Hash(pair)))
+        );
+
+        let err = yaml_load_from_str_with_settings("a: !env PATH", &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 1);
+
+        let docs = yaml_load_marked_from_str_with_settings("a: 1\nb: !pair c=d\n", &settings).unwrap();
+        let pair = match docs[0]["b"].value {
+            MarkedNode::Tagged(_, ref pair) => pair,
+            ref node => panic!("unexpected {:?}", node),
+        };
+        assert_eq!(pair["c"].as_str().unwrap(), "d");
+        assert_eq!((pair["c"].start.line(), pair["c"].start.col()), (2, 9));
+    }
+
+    #[test]
+    fn test_tagged() {
+        let s = "
+- !Ref foo
+- !GetAtt [a, b]
+- !Sub {x: 1}
+- !!str 1
+- ! 1
+- !!seq [1]
+- !!binary aGVsbG8=
+- &a !Ref bar
+- *a
+";
+        let tag = |handle: &str, suffix: &str| Tag { handle: handle.to_owned(), suffix: suffix.to_owned() };
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        assert_eq!(doc[0], Yaml::Tagged(tag("!", "Ref"), Box::new(Yaml::String("foo".to_owned()))));
+        match doc[1] {
+            Yaml::Tagged(ref t, ref v) => {
+                assert_eq!(*t, tag("!", "GetAtt"));
+                assert_eq!(v[1].as_str().unwrap(), "b");
+            }
+            _ => panic!("{:?} is not tagged", doc[1]),
+        }
+        match doc[2] {
+            Yaml::Tagged(ref t, ref v) => {
+                assert_eq!(*t, tag("!", "Sub"));
+                assert_eq!(v["x"].as_i64().unwrap(), 1);
+            }
+            _ => panic!("{:?} is not tagged", doc[2]),
+        }
+        assert_eq!(doc[3].as_str().unwrap(), "1");
+        assert_eq!(doc[4].as_str().unwrap(), "1");
+        assert_eq!(doc[5][0].as_i64().unwrap(), 1);
+        assert_eq!(
+            doc[6],
+            Yaml::Tagged(tag("tag:yaml.org,2002:", "binary"), Box::new(Yaml::String("aGVsbG8=".to_owned())))
+        );
+        assert_eq!(doc[8], doc[7]);
+    }
+
+    #[test]
+    fn test_yaml_version() {
+        let s = "%YAML 1.1\n---\n[yes, 0777, 1_000]\n...\n---\n[yes, 0777, 1_000]\n";
+        let out = yaml_load_from_str(s).unwrap();
+        assert!(out[0][0].as_bool().unwrap());
+        assert_eq!(out[0][1].as_i64().unwrap(), 511);
+        assert_eq!(out[0][2].as_i64().unwrap(), 1000);
+        assert_eq!(out[1][0].as_str().unwrap(), "yes");
+        assert_eq!(out[1][1].as_i64().unwrap(), 777);
+        assert_eq!(out[1][2].as_str().unwrap(), "1_000");
+
+        let settings = YamlStandardSettings::new().schema(Schema::Failsafe);
+        let out = yaml_load_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(out[0][0].as_str().unwrap(), "yes");
+
+        let settings = YamlStandardSettings::new();
+        let (out, warnings) = yaml_load_from_str_with_warnings("%YAML 1.3\n--- 1\n", &settings).unwrap();
+        assert_eq!(out[0].as_i64().unwrap(), 1);
+        assert_eq!(warnings.len(), 1);
+
+        assert!(yaml_load_from_str("%YAML 2.0\n--- 1\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let s = "
+base: &base {a: 1, b: 2}
+other: &other {b: 3, c: 4}
+x:
+  <<: *base
+  b: 5
+y:
+  a: 6
+  <<: [*base, *other]
+z:
+  !!merge <<: *other
+";
+        let settings = YamlStandardSettings::new().merge_keys(true);
+        let doc = yaml_load_doc_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc["x"]["a"].as_i64().unwrap(), 1);
+        assert_eq!(doc["x"]["b"].as_i64().unwrap(), 5);
+        assert_eq!(doc["x"].as_hash().unwrap().len(), 2);
+        assert_eq!(doc["y"]["a"].as_i64().unwrap(), 6);
+        assert_eq!(doc["y"]["b"].as_i64().unwrap(), 2);
+        assert_eq!(doc["y"]["c"].as_i64().unwrap(), 4);
+        assert_eq!(doc["y"].as_hash().unwrap().len(), 3);
+        assert_eq!(doc["z"]["b"].as_i64().unwrap(), 3);
+        assert!(doc["z"]["<<"].is_badvalue());
+
+        // overriding a merged key is not a duplicate
+        let settings = settings.duplicate_keys(DuplicateKeyPolicy::Error);
+        let doc = yaml_load_doc_from_str_with_settings(s, &settings).unwrap();
+        assert_eq!(doc["x"]["b"].as_i64().unwrap(), 5);
+
+        // disabled by default
+        let doc = yaml_load_doc_from_str(s).unwrap();
+        assert_eq!(doc["x"]["<<"]["a"].as_i64().unwrap(), 1);
+        assert!(doc["x"]["a"].is_badvalue());
+        // a quoted `<<` is an ordinary key
+        let doc = yaml_load_doc_from_str_with_settings("'<<': {a: 1}", &settings).unwrap();
+        assert_eq!(doc["<<"]["a"].as_i64().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_merge_keys_error() {
+        let settings = YamlStandardSettings::new().merge_keys(true);
+        // the offending item of a list, not the list
+        let err = yaml_load_from_str_with_settings("a: 1\nb:\n  <<: [{c: 1}, 2]\n", &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 3);
+        assert_eq!(err.marker().col(), 15);
+
+        let err = yaml_load_from_str_with_settings("a:\n  <<: 1\n", &settings).unwrap_err();
+        assert_eq!(err.marker().line(), 2);
+        assert_eq!(err.marker().col(), 6);
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        fn kind(s: &str, settings: &YamlStandardSettings) -> ScanErrorKind {
+            yaml_load_from_str_with_settings(s, settings).unwrap_err().kind()
+        }
+        let default = YamlStandardSettings::new();
+        assert_eq!(kind("a: [1, 2", &default), ScanErrorKind::UnexpectedEof);
+        assert_eq!(kind("a: \"b", &default), ScanErrorKind::UnexpectedEof);
+        assert_eq!(kind("a: [1] 2", &default), ScanErrorKind::UnexpectedToken);
+        assert_eq!(kind("a: `b", &default), ScanErrorKind::UnexpectedCharacter);
+        assert_eq!(kind("a:\n  b: 1\n c: 2", &default), ScanErrorKind::InvalidIndentation);
+        assert_eq!(kind("a: |\n  b\n\tc", &default), ScanErrorKind::TabInIndentation);
+        assert_eq!(kind("a: *b", &default), ScanErrorKind::UnknownAnchor);
+        assert_eq!(kind("a: !x!b c", &default), ScanErrorKind::UnknownTagHandle);
+        assert_eq!(kind("a: \"\\q\"", &default), ScanErrorKind::InvalidEscape);
+        assert_eq!(kind("%YAML 2.0\n--- a", &default), ScanErrorKind::UnsupportedVersion);
+        assert_eq!(kind("%YAML 1.2\n%YAML 1.2\n--- a", &default), ScanErrorKind::InvalidDirective);
+        assert_eq!(kind("a: 1\na: 2", &YamlStandardSettings::new().duplicate_keys(DuplicateKeyPolicy::Error)),
+                   ScanErrorKind::DuplicateKey);
+        assert_eq!(kind("[[1]]", &YamlStandardSettings::new().max_depth(Some(1))),
+                   ScanErrorKind::LimitExceeded(Limit::Depth));
+        assert_eq!(kind("a\n---\nb", &YamlStandardSettings::new().allow_multi_doc(false)),
+                   ScanErrorKind::MultipleDocuments);
+        assert_eq!(kind("a: 1\n<<: 2", &YamlStandardSettings::new().merge_keys(true)), ScanErrorKind::InvalidMerge);
+        assert_eq!(kind("a: yes", &YamlStandardSettings::new().schema(Schema::Json)), ScanErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn test_load_from_bytes() {
+        let s = "a: [1, é]\n---\nb\n";
+        let out = yaml_load_from_slice(s.as_bytes()).unwrap();
+        assert_eq!(out, yaml_load_from_str(s).unwrap());
+        let out = yaml_load_from_reader(::std::io::Cursor::new(s)).unwrap();
+        assert_eq!(out[0]["a"][1].as_str().unwrap(), "é");
+
+        // the decoding error is reported, not the unclosed sequence
+        match yaml_load_from_slice(b"a: 1\nb: [2, \xff]\n") {
+            Err(LoadError::Decode(mark)) => {
+                assert_eq!(mark.line(), 2);
+                assert_eq!(mark.col(), 7);
+            }
+            res => panic!("unexpected {:?}", res),
+        }
+        match yaml_load_from_slice(b"a: [1\n") {
+            Err(LoadError::Scan(err)) => assert_eq!(err.marker().line(), 2),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_load_utf16() {
+        let s = "\u{feff}a: [1, 😀]\n...\n\u{feff}--- b\n";
+        let mut bytes = Vec::new();
+        for u in s.encode_utf16() {
+            bytes.extend_from_slice(&[(u >> 8) as u8, u as u8]);
+        }
+        let out = yaml_load_from_slice(&bytes).unwrap();
+        assert_eq!(out, yaml_load_from_str(s).unwrap());
+        assert_eq!(out[0]["a"][1].as_str().unwrap(), "😀");
+        assert_eq!(out[1].as_str().unwrap(), "b");
+    }
+
+    #[test]
+    fn test_bom_inside_document() {
+        let err = yaml_load_from_str("a: 1\n\u{feff}b: 2\n").unwrap_err();
+        assert_eq!(err.kind(), ScanErrorKind::UnexpectedCharacter);
+        assert_eq!((err.marker().index(), err.marker().line(), err.marker().col()), (5, 2, 0));
+        let err = yaml_load_from_slice("- a\n\u{feff}- b\n".as_bytes()).unwrap_err();
+        assert_eq!((err.marker().line(), err.marker().col()), (2, 0));
+
+        // before a document start marker, and after a document end marker
+        // even with comments in between
+        assert_eq!(yaml_load_from_str("a\n\u{feff}--- b\n").unwrap().len(), 2);
+        assert_eq!(yaml_load_from_str("a\n...\n# c\n\u{feff}%YAML 1.2\n--- b\n").unwrap().len(), 2);
+        let err = yaml_load_from_str("a\n\u{feff}b\n").unwrap_err();
+        assert_eq!((err.marker().line(), err.marker().col()), (2, 0));
+    }
+
+    #[test]
+    fn test_document_iter() {
+        let mut s = String::new();
+        for i in 0..1000 {
+            s.push_str(&format!("---\n- &a {}\n- *a\n", i));
+        }
+        let read = ::std::cell::Cell::new(0);
+        let mut docs = YamlDocumentIter::new(s.chars().inspect(|_| read.set(read.get() + 1)));
+        let doc = docs.next().unwrap().unwrap();
+        assert_eq!(doc[1].as_i64().unwrap(), 0);
+        assert!(read.get() < 100);
+        assert_eq!(docs.count(), 999);
+
+        let settings = YamlStandardSettings::new().max_documents(Some(2));
+        let docs: Vec<_> = YamlDocumentIter::new_with_settings("a\n---\nb\n---\nc\n".chars(), &settings).collect();
+        assert_eq!(docs.len(), 3);
+        assert!(docs[1].is_ok());
+        assert!(docs[2].is_err());
+
+        assert_eq!(YamlDocumentIter::new("".chars()).count(), 0);
+        assert_eq!(YamlDocumentIter::new("# comment\n".chars()).count(), 0);
+    }
 }